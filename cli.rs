@@ -0,0 +1,275 @@
+use std::env;
+
+use crate::get_env;
+
+pub const USAGE: &str = "Usage: rust_evm_indexer [COMMAND] [OPTIONS]
+
+Commands:
+  run [INDEXER]                          Run one indexer
+  run-all                                Run every active indexer
+  backfill [INDEXER] [--from N] [--to N] Index history with concurrent workers
+  reindex [INDEXER] --from N [--to N]    Index again from a block, or only a range of blocks
+  dry-run [INDEXER] [--from N] [--to N]  Print what would be stored as JSON lines
+  list                                   Show indexers, networks and tokens
+  status                                 Show lag of every indexer behind its network
+  help                                   Show this message
+
+Options:
+  --config PATH                          Take networks, tokens and indexers from a TOML file
+                                         instead of the Django tables, defaults to INDEXER_CONFIG
+
+INDEXER defaults to INDEXER_NAME. Without a command INDEXER_MODE picks one,
+dry-run range defaults to DRY_RUN_FROM_BLOCK and DRY_RUN_TO_BLOCK.";
+
+pub struct Cli {
+    pub command: Command,
+    pub config_path: Option<String>,
+}
+
+pub enum Command {
+    Run { indexer_name: String },
+    RunAll,
+    Backfill { indexer_name: String, from_block: Option<u64>, to_block: Option<u64> },
+    Reindex { indexer_name: String, from_block: u64, to_block: Option<u64> },
+    DryRun { indexer_name: String, from_block: Option<u64>, to_block: Option<u64> },
+    List,
+    Status,
+    Help,
+}
+
+/// Arguments of a subcommand: at most one positional indexer name and `--flag value` pairs.
+struct Arguments {
+    indexer_name: Option<String>,
+    from_block: Option<u64>,
+    to_block: Option<u64>,
+}
+
+pub fn parse_args(args: &[String]) -> Result<Cli, String> {
+    let (config_path, args) = take_config_path(args)?;
+    Ok(Cli {
+        command: parse_command(&args)?,
+        config_path: config_path.or_else(|| get_env("INDEXER_CONFIG").ok().filter(|path| !path.is_empty())),
+    })
+}
+
+/// Config option applies to every command, so it is taken out before the command is parsed.
+fn take_config_path(args: &[String]) -> Result<(Option<String>, Vec<String>), String> {
+    let mut config_path = None;
+    let mut rest = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            match args.next() {
+                Some(path) => config_path = Some(path.clone()),
+                None => return Err(String::from("--config needs a path")),
+            }
+        } else if let Some(path) = arg.strip_prefix("--config=") {
+            config_path = Some(path.to_string());
+        } else {
+            rest.push(arg.clone());
+        }
+    }
+    Ok((config_path, rest))
+}
+
+fn parse_command(args: &[String]) -> Result<Command, String> {
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command.clone(), rest),
+        // environment only deployments keep working as before
+        None => (mode_from_env(), &[] as &[String]),
+    };
+    let arguments = parse_arguments(rest)?;
+    match command.as_str() {
+        "run" => Ok(Command::Run {
+            indexer_name: get_indexer_name(arguments.indexer_name)?,
+        }),
+        "run-all" => Ok(Command::RunAll),
+        "backfill" => Ok(Command::Backfill {
+            indexer_name: get_indexer_name(arguments.indexer_name)?,
+            from_block: arguments.from_block,
+            to_block: arguments.to_block,
+        }),
+        "reindex" => match arguments.from_block {
+            Some(from_block) => Ok(Command::Reindex {
+                indexer_name: get_indexer_name(arguments.indexer_name)?,
+                from_block,
+                to_block: arguments.to_block,
+            }),
+            None => Err(String::from("reindex needs --from block")),
+        },
+        "dry-run" => Ok(Command::DryRun {
+            indexer_name: get_indexer_name(arguments.indexer_name)?,
+            from_block: arguments.from_block.map_or_else(|| get_env_block("DRY_RUN_FROM_BLOCK"), |block| Ok(Some(block)))?,
+            to_block: arguments.to_block.map_or_else(|| get_env_block("DRY_RUN_TO_BLOCK"), |block| Ok(Some(block)))?,
+        }),
+        "list" => Ok(Command::List),
+        "status" => Ok(Command::Status),
+        "help" | "--help" | "-h" => Ok(Command::Help),
+        _ => Err(format!("Unknown command {command}")),
+    }
+}
+
+fn mode_from_env() -> String {
+    match get_env("INDEXER_MODE").as_deref() {
+        Ok("supervisor") => String::from("run-all"),
+        Ok("backfill") => String::from("backfill"),
+        Ok("dry-run") => String::from("dry-run"),
+        _ => String::from("run"),
+    }
+}
+
+fn parse_arguments(args: &[String]) -> Result<Arguments, String> {
+    let mut arguments = Arguments {
+        indexer_name: None,
+        from_block: None,
+        to_block: None,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        // both `--from 10` and `--from=10` are accepted
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if arg.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        match flag {
+            "--from" | "--to" => {
+                let value = match inline_value.or_else(|| args.next().cloned()) {
+                    Some(value) => value,
+                    None => return Err(format!("{flag} needs a block number")),
+                };
+                let block = parse_block(flag, &value)?;
+                if flag == "--from" {
+                    arguments.from_block = Some(block);
+                } else {
+                    arguments.to_block = Some(block);
+                }
+            }
+            _ if flag.starts_with('-') => return Err(format!("Unknown option {flag}")),
+            _ if arguments.indexer_name.is_none() => arguments.indexer_name = Some(arg.clone()),
+            _ => return Err(format!("Unexpected argument {arg}")),
+        }
+    }
+    if let (Some(from_block), Some(to_block)) = (arguments.from_block, arguments.to_block) {
+        if to_block < from_block {
+            return Err(format!("Range {from_block} to {to_block} is empty"));
+        }
+    }
+    Ok(arguments)
+}
+
+fn get_indexer_name(indexer_name: Option<String>) -> Result<String, String> {
+    match indexer_name {
+        Some(indexer_name) => Ok(indexer_name),
+        None => get_env("INDEXER_NAME").map_err(|_| String::from("Indexer name is neither given nor set in INDEXER_NAME")),
+    }
+}
+
+fn parse_block(name: &str, value: &str) -> Result<u64, String> {
+    match value.parse::<u64>() {
+        Ok(block) => Ok(block),
+        Err(e) => Err(format!("{name} should be a block number, found {value}: {e}")),
+    }
+}
+
+fn get_env_block(key: &str) -> Result<Option<u64>, String> {
+    match env::var(key) {
+        Ok(value) if value.is_empty() => Ok(None),
+        Ok(value) => parse_block(key, &value).map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::{parse_args, Command};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_flags_with_separate_and_inline_values() {
+        let cli = parse_args(&args(&["backfill", "usdt", "--from", "10", "--to=20"])).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Backfill { indexer_name, from_block: Some(10), to_block: Some(20) } if indexer_name == "usdt"
+        ));
+    }
+
+    #[test]
+    fn takes_config_anywhere_in_arguments() {
+        for arguments in [
+            args(&["--config", "indexers.toml", "reindex", "usdt", "--from", "5"]),
+            args(&["reindex", "usdt", "--config=indexers.toml", "--from", "5"]),
+            args(&["reindex", "usdt", "--from", "5", "--config", "indexers.toml"]),
+        ] {
+            let cli = parse_args(&arguments).unwrap();
+            assert_eq!(cli.config_path.as_deref(), Some("indexers.toml"));
+            assert!(matches!(
+                cli.command,
+                Command::Reindex { indexer_name, from_block: 5, to_block: None } if indexer_name == "usdt"
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        let cases = [
+            (args(&["backfill", "usdt", "--from="]), "--from should be a block number, found : "),
+            (args(&["backfill", "usdt", "--to"]), "--to needs a block number"),
+            (args(&["backfill", "usdt", "--from", "20", "--to", "10"]), "Range 20 to 10 is empty"),
+            (args(&["reindex", "usdt"]), "reindex needs --from block"),
+            (args(&["run", "usdt", "--step", "5"]), "Unknown option --step"),
+            (args(&["run", "usdt", "usdc"]), "Unexpected argument usdc"),
+            (args(&["run", "usdt", "--config"]), "--config needs a path"),
+            (args(&["start"]), "Unknown command start"),
+        ];
+        for (arguments, reason) in cases {
+            match parse_args(&arguments) {
+                Ok(_) => panic!("{arguments:?} should be rejected"),
+                Err(e) => assert!(e.starts_with(reason), "{arguments:?} failed with {e}"),
+            }
+        }
+    }
+
+    // the only test touching the environment, as tests share it while running in parallel
+    #[test]
+    fn falls_back_to_environment() {
+        env::set_var("INDEXER_NAME", "usdt");
+        env::set_var("INDEXER_CONFIG", "from-env.toml");
+        env::set_var("INDEXER_MODE", "dry-run");
+        env::set_var("DRY_RUN_FROM_BLOCK", "100");
+        env::set_var("DRY_RUN_TO_BLOCK", "");
+
+        let cli = parse_args(&[]).unwrap();
+        assert_eq!(cli.config_path.as_deref(), Some("from-env.toml"));
+        assert!(matches!(
+            cli.command,
+            Command::DryRun { indexer_name, from_block: Some(100), to_block: None } if indexer_name == "usdt"
+        ));
+
+        let cli = parse_args(&args(&["dry-run", "usdc", "--from", "7", "--config", "given.toml"])).unwrap();
+        assert_eq!(cli.config_path.as_deref(), Some("given.toml"));
+        assert!(matches!(
+            cli.command,
+            Command::DryRun { indexer_name, from_block: Some(7), to_block: None } if indexer_name == "usdc"
+        ));
+
+        env::set_var("INDEXER_CONFIG", "");
+        env::set_var("INDEXER_MODE", "supervisor");
+        let cli = parse_args(&[]).unwrap();
+        assert_eq!(cli.config_path, None);
+        assert!(matches!(cli.command, Command::RunAll));
+
+        env::remove_var("INDEXER_NAME");
+        assert!(parse_args(&args(&["run"])).is_err());
+        env::set_var("DRY_RUN_FROM_BLOCK", "latest");
+        assert!(parse_args(&args(&["dry-run", "usdt"])).is_err());
+
+        for key in ["INDEXER_CONFIG", "INDEXER_MODE", "DRY_RUN_FROM_BLOCK", "DRY_RUN_TO_BLOCK"] {
+            env::remove_var(key);
+        }
+    }
+}
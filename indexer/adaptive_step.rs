@@ -0,0 +1,114 @@
+use log::info;
+
+// consecutive successful ranges needed before step is grown again
+const GROW_AFTER_SUCCESSES: u32 = 5;
+
+/// Block step which is halved when provider rejects a range as too large
+/// and doubled back up to `Network.max_step` after a run of successes.
+pub struct AdaptiveStep {
+    step: Option<u64>,
+    successes: u32,
+}
+
+impl AdaptiveStep {
+    pub fn new() -> Self {
+        Self {
+            step: None,
+            successes: 0,
+        }
+    }
+
+    pub fn current(&self, max_step: u64) -> u64 {
+        match self.step {
+            Some(step) => std::cmp::min(step, max_step),
+            None => max_step,
+        }
+    }
+
+    /// Returns false if step cannot be decreased anymore.
+    pub fn shrink(&mut self, max_step: u64) -> bool {
+        let current = self.current(max_step);
+        if current <= 1 {
+            return false;
+        }
+        let shrunk = current / 2;
+        info!("Block step decreased from {current} to {shrunk}");
+        self.step = Some(shrunk);
+        self.successes = 0;
+        true
+    }
+
+    pub fn record_success(&mut self, max_step: u64) {
+        let current = self.current(max_step);
+        if current >= max_step {
+            self.step = None;
+            return;
+        }
+        self.successes += 1;
+        if self.successes < GROW_AFTER_SUCCESSES {
+            return;
+        }
+        let grown = std::cmp::min(current.saturating_mul(2), max_step);
+        info!("Block step increased from {current} to {grown} after {} successful ranges", self.successes);
+        self.step = Some(grown);
+        self.successes = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AdaptiveStep, GROW_AFTER_SUCCESSES};
+
+    #[test]
+    fn starts_at_max_step() {
+        assert_eq!(AdaptiveStep::new().current(1000), 1000);
+    }
+
+    #[test]
+    fn halves_step_down_to_one_block() {
+        let mut step = AdaptiveStep::new();
+        assert!(step.shrink(5));
+        assert_eq!(step.current(5), 2);
+        assert!(step.shrink(5));
+        assert_eq!(step.current(5), 1);
+        assert!(!step.shrink(5));
+        assert_eq!(step.current(5), 1);
+    }
+
+    #[test]
+    fn grows_back_after_run_of_successes() {
+        let mut step = AdaptiveStep::new();
+        step.shrink(1000);
+        step.shrink(1000);
+        assert_eq!(step.current(1000), 250);
+        for _ in 0..GROW_AFTER_SUCCESSES - 1 {
+            step.record_success(1000);
+        }
+        assert_eq!(step.current(1000), 250);
+        step.record_success(1000);
+        assert_eq!(step.current(1000), 500);
+        for _ in 0..GROW_AFTER_SUCCESSES {
+            step.record_success(1000);
+        }
+        assert_eq!(step.current(1000), 1000);
+    }
+
+    #[test]
+    fn shrink_resets_success_run() {
+        let mut step = AdaptiveStep::new();
+        step.shrink(1000);
+        for _ in 0..GROW_AFTER_SUCCESSES - 1 {
+            step.record_success(1000);
+        }
+        step.shrink(1000);
+        step.record_success(1000);
+        assert_eq!(step.current(1000), 250);
+    }
+
+    #[test]
+    fn follows_lowered_max_step() {
+        let mut step = AdaptiveStep::new();
+        step.shrink(1000);
+        assert_eq!(step.current(100), 100);
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use log::{info, warn};
+use tokio::sync::watch;
+use tokio_postgres::Client;
+use web3::Web3;
+
+use crate::models::{migrate, Indexer, Network, Token};
+
+use super::{
+    backfill::{backfill, BackfillOptions},
+    fetcher::get_block_number,
+    shutdown::ExitStatus,
+    transports::TransportPool,
+};
+
+/// Prints every indexer, network and token known to the database.
+pub async fn list(client: &mut Client) -> ExitStatus {
+    let (indexers, networks, tokens) = match (
+        Indexer::load_all_from_db(client).await,
+        Network::load_all_from_db(client).await,
+        Token::load_all_from_db(client).await,
+    ) {
+        (Ok(indexers), Ok(networks), Ok(tokens)) => (indexers, networks, tokens),
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+            warn!("During loading indexers, networks and tokens occurred {}", e.reason);
+            return ExitStatus::Failed;
+        }
+    };
+    let network_names: HashMap<i64, &String> = networks.iter().map(|network| (network.id, &network.name)).collect();
+    let network_name = |network_id: i64| network_names.get(&network_id).map_or("?", |name| name.as_str());
+    println!("Indexers:");
+    println!("{:<6} {:<32} {:<12} {:<16} {:<10} {:>12}", "ID", "NAME", "STATUS", "NETWORK", "STRATEGY", "LAST BLOCK");
+    for indexer in indexers.iter() {
+        println!(
+            "{:<6} {:<32} {:<12} {:<16} {:<10} {:>12}",
+            indexer.id,
+            indexer.name,
+            indexer.status,
+            network_name(indexer.network_id),
+            indexer.strategy,
+            indexer.last_block
+        );
+    }
+    println!();
+    println!("Networks:");
+    println!("{:<6} {:<16} {:>10} {:>6} {:>14} {:<8}", "ID", "NAME", "CHAIN ID", "RPCS", "CONFIRMATIONS", "TRACES");
+    for network in networks.iter() {
+        println!(
+            "{:<6} {:<16} {:>10} {:>6} {:>14} {:<8}",
+            network.id,
+            network.name,
+            network.chain_id,
+            network.get_rpc_urls().len(),
+            network.confirmations,
+            network.trace_api.map_or(String::from("-"), |trace_api| trace_api.to_string())
+        );
+    }
+    println!();
+    println!("Tokens:");
+    println!("{:<6} {:<32} {:<8} {:<42} {:<16}", "ID", "NAME", "TYPE", "ADDRESS", "NETWORK");
+    for token in tokens.iter() {
+        println!(
+            "{:<6} {:<32} {:<8} {:<42} {:<16}",
+            token.id,
+            token.name,
+            token.token_type,
+            token.address,
+            network_name(token.network_id)
+        );
+    }
+    ExitStatus::Completed
+}
+
+/// Prints how far behind the confirmed head of its network every indexer is.
+pub async fn status(client: &mut Client) -> ExitStatus {
+    let (indexers, networks) = match (Indexer::load_all_from_db(client).await, Network::load_all_from_db(client).await) {
+        (Ok(indexers), Ok(networks)) => (indexers, networks),
+        (Err(e), _) | (_, Err(e)) => {
+            warn!("During loading indexers and networks occurred {}", e.reason);
+            return ExitStatus::Failed;
+        }
+    };
+    let transports = TransportPool::new();
+    let mut heads: HashMap<i64, Option<u64>> = HashMap::new();
+    for network in networks.iter() {
+        if !indexers.iter().any(|indexer| indexer.network_id == network.id) {
+            continue;
+        }
+        let head = match transports.get(network).await {
+            Ok(transport) => get_block_number(&Web3::new(transport), network).await,
+            Err(e) => Err(e),
+        };
+        match head {
+            Ok(head) => heads.insert(network.id, Some(head)),
+            Err(e) => {
+                warn!("Head of network {} is unknown: {}", network.name, e.reason);
+                heads.insert(network.id, None)
+            }
+        };
+    }
+    let network_names: HashMap<i64, &String> = networks.iter().map(|network| (network.id, &network.name)).collect();
+    println!("{:<32} {:<12} {:<16} {:>12} {:>12} {:>10}", "INDEXER", "STATUS", "NETWORK", "LAST BLOCK", "HEAD", "LAG");
+    for indexer in indexers.iter() {
+        let head = heads.get(&indexer.network_id).copied().flatten();
+        println!(
+            "{:<32} {:<12} {:<16} {:>12} {:>12} {:>10}",
+            indexer.name,
+            indexer.status,
+            network_names.get(&indexer.network_id).map_or("?", |name| name.as_str()),
+            indexer.last_block,
+            head.map_or(String::from("?"), |head| head.to_string()),
+            head.map_or(String::from("?"), |head| head.saturating_sub(indexer.last_block).to_string())
+        );
+    }
+    ExitStatus::Completed
+}
+
+/// Without `to_block` asks the running indexer to roll back and index forward from `from_block`.
+/// With it replaces transfers of just that range in place, leaving the rest of the indexer as is.
+/// Such range has to end below the reorg window, otherwise nothing is replaced and the command fails.
+pub async fn reindex(
+    client: &mut Client,
+    indexer_name: &String,
+    from_block: u64,
+    to_block: Option<u64>,
+    shutdown: watch::Receiver<bool>,
+) -> ExitStatus {
+    if let Some(to_block) = to_block {
+        let options = BackfillOptions {
+            from_block: Some(from_block),
+            to_block: Some(to_block),
+            replace: true,
+            ..BackfillOptions::from_env()
+        };
+        return backfill(client, indexer_name, options, shutdown).await;
+    }
+    if let Err(e) = migrate(client).await {
+        warn!("On migrating database occurred {}", e.reason);
+        return ExitStatus::Failed;
+    }
+    let mut indexer = match Indexer::load_from_db(client, indexer_name).await {
+        Ok(indexer) => indexer,
+        Err(e) => {
+            warn!("On instantiating indexer {} occurred", e.reason);
+            return ExitStatus::Failed;
+        }
+    };
+    if from_block > indexer.last_block {
+        warn!(
+            "Indexer {} has not reached block {from_block} yet, it is at {}",
+            indexer.name, indexer.last_block
+        );
+        return ExitStatus::Failed;
+    }
+    match indexer.request_reindex(client, from_block).await {
+        Ok(()) => {
+            info!("Indexer {} will reindex from block {from_block} on its next cycle", indexer.name);
+            ExitStatus::Completed
+        }
+        Err(e) => {
+            warn!("During requesting reindex of {} occurred {}", indexer.name, e.reason);
+            ExitStatus::Failed
+        }
+    }
+}
@@ -0,0 +1,492 @@
+use std::{
+    collections::VecDeque,
+    env,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use log::{error, info, warn};
+use tokio::sync::{
+    mpsc::{self, UnboundedSender},
+    watch,
+};
+use tokio_postgres::Client;
+use web3::{types::H256, Web3};
+
+use crate::{
+    init_db_client,
+    models::{migrate, BackfillChunk, Indexer, IndexerStatus, Network, Token, TokenTransfer},
+};
+
+use super::{
+    adaptive_step::AdaptiveStep,
+    backoff::Backoff,
+    commons::{CycleError, IndexerError},
+    cycle::{get_network, get_payload_topics, get_tokens, parse_range, reconnect_if_closed, skip_to_start_block},
+    enrichment::Enricher,
+    fetcher::{fetch_range, get_block_number},
+    metrics,
+    reorg::MAX_REORG_DEPTH,
+    shutdown::ExitStatus,
+    traces::get_trace_api,
+    transports::{RpcTransport, TransportPool},
+};
+
+const DEFAULT_WORKERS: usize = 4;
+const DEFAULT_CHUNK_BLOCKS: u64 = 10_000;
+// attempts of one chunk before its worker gives up
+const MAX_CHUNK_ATTEMPTS: u32 = 5;
+
+/// Everything workers share, loaded once before chunks are handed out.
+struct BackfillJob {
+    indexer_name: String,
+    web3: Web3<RpcTransport>,
+    tokens: Vec<Token>,
+    three_payload_topics: [Option<Vec<H256>>; 3],
+    network: Network,
+    chunks: Mutex<VecDeque<(u64, u64)>>,
+    replace: bool,
+    stop: watch::Receiver<bool>,
+}
+
+/// Range and parallelism of a backfill, unset bounds follow the indexer.
+pub struct BackfillOptions {
+    pub from_block: Option<u64>,
+    pub to_block: Option<u64>,
+    // transfers already stored in the range are replaced instead of upserted
+    pub replace: bool,
+    pub workers: usize,
+    pub chunk_blocks: u64,
+}
+
+impl BackfillOptions {
+    pub fn from_env() -> Self {
+        Self {
+            from_block: None,
+            to_block: None,
+            replace: false,
+            workers: get_env_number("BACKFILL_WORKERS", DEFAULT_WORKERS as u64) as usize,
+            chunk_blocks: get_env_number("BACKFILL_CHUNK_BLOCKS", DEFAULT_CHUNK_BLOCKS),
+        }
+    }
+}
+
+/// Indexes history of the indexer up to the reorg window with concurrent workers,
+/// so the regular cycle only follows the head afterwards.
+pub async fn backfill(
+    client: &mut Client,
+    indexer_name: &String,
+    options: BackfillOptions,
+    shutdown: watch::Receiver<bool>,
+) -> ExitStatus {
+    if let Err(e) = migrate(client).await {
+        warn!("On migrating database occurred {}", e.reason);
+        return ExitStatus::Failed;
+    }
+    let mut indexer = match Indexer::load_from_db(client, indexer_name).await {
+        Ok(indexer) => indexer,
+        Err(e) => {
+            warn!("On instantiating indexer {} occurred", e.reason);
+            return ExitStatus::Failed;
+        }
+    };
+    match run_backfill(client, &mut indexer, options, shutdown.clone()).await {
+        Ok(()) if *shutdown.borrow() => {
+            reconnect_if_closed(client).await;
+            if let Err(e) = indexer.update_status(client, IndexerStatus::Interrupted).await {
+                warn!("During marking indexer {} interrupted occurred {}", indexer.name, e.reason);
+            }
+            info!("Backfill of {} interrupted at block {}", indexer.name, indexer.last_block);
+            ExitStatus::Completed
+        }
+        Ok(()) => {
+            info!("Backfill of {} finished at block {}", indexer.name, indexer.last_block);
+            ExitStatus::Completed
+        }
+        Err(e) => {
+            error!(
+                "Backfill of {} stopped at block {}: {}",
+                indexer.name, indexer.last_block, e.reason
+            );
+            ExitStatus::Failed
+        }
+    }
+}
+
+async fn run_backfill(
+    client: &mut Client,
+    indexer: &mut Indexer,
+    options: BackfillOptions,
+    stop: watch::Receiver<bool>,
+) -> Result<(), IndexerError> {
+    let workers = options.workers;
+    let chunk_blocks = options.chunk_blocks;
+    skip_to_start_block(client, indexer).await?;
+    let network = get_network(client, indexer).await?;
+    let transports = TransportPool::new();
+    let web3 = Web3::new(transports.get(&network).await?);
+    let chain_head = get_block_number(&web3, &network).await?;
+    metrics::set_progress(&indexer.name, &network.name, Some(chain_head), indexer.last_block);
+    // the last blocks may still be reorged, they are left for the regular cycle
+    let head = chain_head.saturating_sub(MAX_REORG_DEPTH);
+    let head = indexer.end_block.map_or(head, |end_block| std::cmp::min(head, end_block));
+    // requested range is done in full or not at all, a range cut short would still look finished
+    let head = match options.to_block {
+        Some(to_block) if to_block > head => {
+            let hint = if options.replace {
+                "can be indexed again only by reindex without --to, which rolls the indexer back"
+            } else {
+                "are left for the regular cycle"
+            };
+            return Err(IndexerError {
+                reason: format!(
+                    "Backfill reaches block {head} at most ({MAX_REORG_DEPTH} blocks below head {chain_head} \
+                     or the end block), blocks up to {to_block} {hint}"
+                ),
+            });
+        }
+        Some(to_block) => to_block,
+        None => head,
+    };
+    let from_block = options.from_block.unwrap_or(indexer.last_block);
+    // last block only moves through contiguous chunks, blocks in between would never be indexed
+    if from_block > indexer.last_block {
+        return Err(IndexerError {
+            reason: format!(
+                "Backfill from block {from_block} would skip blocks {} to {from_block} after last indexed block, \
+                 backfill from block {} or set start_block instead",
+                indexer.last_block, indexer.last_block
+            ),
+        });
+    }
+    let three_payload_topics = match get_payload_topics(indexer) {
+        Ok(topics) => topics,
+        Err(CycleError::Transient(e)) | Err(CycleError::Fatal(e)) => return Err(e),
+    };
+    let tokens = get_tokens(client, indexer).await?;
+    // replaced range is indexed anew, whatever was completed before
+    let completed = if options.replace {
+        vec![]
+    } else {
+        match BackfillChunk::load_from_db(client, indexer).await {
+            Ok(completed) => completed,
+            Err(e) => return Err(IndexerError { reason: e.reason }),
+        }
+    };
+    let chunks = plan_chunks(from_block, head, chunk_blocks, &completed);
+    info!(
+        "Backfilling {} from block {from_block} to {head}: {} chunks left, {} already completed, {workers} workers",
+        indexer.name,
+        chunks.len(),
+        completed.len()
+    );
+    let job = Arc::new(BackfillJob {
+        indexer_name: indexer.name.clone(),
+        web3,
+        tokens,
+        three_payload_topics,
+        network,
+        chunks: Mutex::new(chunks.into()),
+        replace: options.replace,
+        stop,
+    });
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let mut handles = vec![];
+    for worker in 0..workers {
+        let (job, sender) = (job.clone(), sender.clone());
+        handles.push(tokio::spawn(async move { run_worker(worker, job, sender).await }));
+    }
+    drop(sender);
+    let mut finished: Vec<(u64, u64)> = completed.iter().map(|chunk| (chunk.from_block, chunk.to_block)).collect();
+    advance_last_block(client, indexer, &mut finished).await?;
+    while let Some(chunk) = receiver.recv().await {
+        finished.push(chunk);
+        advance_last_block(client, indexer, &mut finished).await?;
+        metrics::set_progress(&indexer.name, &job.network.name, None, indexer.last_block);
+    }
+    let mut failed_workers = 0;
+    for handle in handles {
+        if !matches!(handle.await, Ok(Ok(()))) {
+            failed_workers += 1;
+        }
+    }
+    if failed_workers > 0 {
+        return Err(IndexerError {
+            reason: format!("{failed_workers} workers failed, completed chunks are kept for the next run"),
+        });
+    }
+    if indexer.reached_end_block() {
+        if let Err(e) = indexer.update_status(client, IndexerStatus::Finished).await {
+            warn!("During marking indexer {} finished occurred {}", indexer.name, e.reason);
+        }
+    }
+    Ok(())
+}
+
+/// Splits `[from_block, head]` into chunks aligned to `chunk_blocks`, so boundaries survive restarts,
+/// skipping what completed chunks already cover.
+fn plan_chunks(from_block: u64, head: u64, chunk_blocks: u64, completed: &[BackfillChunk]) -> Vec<(u64, u64)> {
+    let mut chunks = vec![];
+    let mut chunk_start = from_block;
+    while chunk_start < head {
+        let chunk_end = std::cmp::min((chunk_start / chunk_blocks + 1) * chunk_blocks, head);
+        let mut start = chunk_start;
+        while let Some(done) = completed.iter().find(|done| done.from_block <= start && done.to_block > start) {
+            start = done.to_block;
+        }
+        if start < chunk_end {
+            chunks.push((start, chunk_end));
+        }
+        chunk_start = chunk_end;
+    }
+    chunks
+}
+
+/// Moves indexer last block over the contiguous prefix of finished chunks.
+async fn advance_last_block(
+    client: &mut Client,
+    indexer: &mut Indexer,
+    finished: &mut Vec<(u64, u64)>,
+) -> Result<(), IndexerError> {
+    let last_block = take_contiguous_prefix(indexer.last_block, finished);
+    if last_block == indexer.last_block {
+        return Ok(());
+    }
+    reconnect_if_closed(client).await;
+    if let Err(e) = indexer.update_last_block(client, last_block).await {
+        return Err(IndexerError {
+            reason: format!("During moving indexer to block {last_block} occurred {}", e.reason),
+        });
+    }
+    info!("Backfill moved indexer {} to block {last_block}", indexer.name);
+    if let Err(e) = BackfillChunk::delete_covered(client, indexer).await {
+        warn!("{}", e.reason);
+    }
+    Ok(())
+}
+
+/// Consumes finished chunks continuing from `last_block` and returns the block they reach.
+/// Chunks behind a gap stay in `finished` until the gap is filled.
+fn take_contiguous_prefix(mut last_block: u64, finished: &mut Vec<(u64, u64)>) -> u64 {
+    while let Some(i) = finished.iter().position(|chunk| chunk.0 <= last_block && chunk.1 > last_block) {
+        last_block = finished.swap_remove(i).1;
+    }
+    last_block
+}
+
+async fn run_worker(
+    worker: usize,
+    job: Arc<BackfillJob>,
+    sender: UnboundedSender<(u64, u64)>,
+) -> Result<(), IndexerError> {
+    let mut client = match init_db_client().await {
+        Ok(client) => client,
+        Err(e) => return Err(IndexerError { reason: e }),
+    };
+    // worker loads its own indexer, the coordinator keeps moving last block of the shared one
+    let indexer = match Indexer::load_from_db(&mut client, &job.indexer_name).await {
+        Ok(indexer) => indexer,
+        Err(e) => return Err(IndexerError { reason: e.reason }),
+    };
+    let mut step = AdaptiveStep::new();
+    let mut enricher = Enricher::new();
+    enricher.configure(&job.network, &indexer);
+    let mut stop = job.stop.clone();
+    loop {
+        // chunk in progress is committed or dropped as a whole, so stopping between chunks is clean
+        if *stop.borrow() {
+            return Ok(());
+        }
+        let Some(chunk) = job.chunks.lock().unwrap().pop_front() else {
+            return Ok(());
+        };
+        let mut backoff = Backoff::new();
+        loop {
+            match backfill_chunk(&mut client, &indexer, &job, chunk, &mut step, &mut enricher).await {
+                Ok(()) => break,
+                Err(CycleError::Transient(e)) if backoff.attempt() + 1 < MAX_CHUNK_ATTEMPTS => {
+                    let delay = backoff.next_delay();
+                    warn!(
+                        "Worker {worker} failed chunk {} to {}: {}. Retry #{} in {} ms",
+                        chunk.0,
+                        chunk.1,
+                        e.reason,
+                        backoff.attempt(),
+                        delay.as_millis()
+                    );
+                    tokio::select! {
+                        _ = tokio::time::sleep(delay) => {}
+                        Ok(()) = stop.changed() => return Ok(()),
+                    }
+                    reconnect_if_closed(&mut client).await;
+                }
+                Err(CycleError::Transient(e)) | Err(CycleError::Fatal(e)) => {
+                    error!("Worker {worker} gave up on chunk {} to {}: {}", chunk.0, chunk.1, e.reason);
+                    return Err(e);
+                }
+            }
+        }
+        info!("Worker {worker} completed chunk {} to {}", chunk.0, chunk.1);
+        if sender.send(chunk).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// Fetches the whole chunk and commits its transfers together with the chunk checkpoint.
+async fn backfill_chunk(
+    client: &mut Client,
+    indexer: &Indexer,
+    job: &BackfillJob,
+    chunk: (u64, u64),
+    step: &mut AdaptiveStep,
+    enricher: &mut Enricher,
+) -> Result<(), CycleError> {
+    let mut token_transfers = vec![];
+    // fetched and saved counts of every token, parsed ranges keep the order of job tokens
+    let mut token_counts = vec![(0, 0); job.tokens.len()];
+    let mut ranges = 0;
+    let mut from_block = chunk.0;
+    loop {
+        let fetched = fetch_range(
+            &job.web3,
+            &job.tokens,
+            &job.three_payload_topics,
+            from_block,
+            chunk.1,
+            job.network.max_step,
+            step,
+            enricher,
+            get_trace_api(&job.network, indexer),
+        )
+        .await?;
+        let parsed = parse_range(&indexer.name, &job.network.name, &job.tokens, &fetched);
+        for (i, (token, transactions)) in parsed.into_iter().enumerate() {
+            let saved_before = token_transfers.len();
+            for transaction in transactions.iter() {
+                token_transfers.extend(TokenTransfer::build_from_transaction(transaction, token, indexer));
+            }
+            token_counts[i].0 += transactions.len() as u64;
+            token_counts[i].1 += (token_transfers.len() - saved_before) as u64;
+        }
+        ranges += 1;
+        if fetched.range.1 >= chunk.1 {
+            break;
+        }
+        from_block = fetched.range.1;
+    }
+    let started = Instant::now();
+    let mut db_tx = match client.transaction().await {
+        Ok(db_tx) => db_tx,
+        Err(e) => {
+            return Err(CycleError::Transient(IndexerError {
+                reason: format!("During preparing backfill transaction occurred {e}"),
+            }))
+        }
+    };
+    if job.replace {
+        if let Err(e) = TokenTransfer::delete_in_range(&mut db_tx, indexer, chunk.0, chunk.1).await {
+            return Err(CycleError::Transient(IndexerError { reason: e.reason }));
+        }
+    }
+    for token_transfer in token_transfers.iter() {
+        if let Err(e) = token_transfer.add_to_db_tx(&mut db_tx).await {
+            return Err(CycleError::Transient(IndexerError { reason: e.reason }));
+        }
+    }
+    let checkpoint = BackfillChunk {
+        indexer_id: indexer.id,
+        from_block: chunk.0,
+        to_block: chunk.1,
+    };
+    if let Err(e) = checkpoint.add_to_db_tx(&mut db_tx).await {
+        return Err(CycleError::Transient(IndexerError { reason: e.reason }));
+    }
+    if let Err(e) = db_tx.commit().await {
+        return Err(CycleError::Transient(IndexerError {
+            reason: format!("During commit of backfill chunk occurred {e}"),
+        }));
+    }
+    let labels = [("indexer", indexer.name.as_str()), ("network", job.network.name.as_str())];
+    metrics::observe("evm_indexer_db_commit_duration_seconds", &labels, started.elapsed());
+    metrics::inc_counter("evm_indexer_ranges_processed_total", &labels, ranges);
+    metrics::set_gauge("evm_indexer_step_blocks", &labels, step.current(job.network.max_step) as f64);
+    for (token, (fetched_count, saved_count)) in job.tokens.iter().zip(token_counts) {
+        let labels = [
+            ("indexer", indexer.name.as_str()),
+            ("network", job.network.name.as_str()),
+            ("token", token.name.as_str()),
+        ];
+        metrics::inc_counter("evm_indexer_logs_fetched_total", &labels, fetched_count);
+        metrics::inc_counter("evm_indexer_transfers_saved_total", &labels, saved_count);
+    }
+    Ok(())
+}
+
+fn get_env_number(key: &str, default: u64) -> u64 {
+    match env::var(key) {
+        Ok(value) => match value.parse::<u64>() {
+            Ok(number) if number > 0 => number,
+            _ => {
+                warn!("{key} should be a positive number, using {default}");
+                default
+            }
+        },
+        Err(_) => default,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::BackfillChunk;
+
+    use super::{plan_chunks, take_contiguous_prefix};
+
+    fn completed(from_block: u64, to_block: u64) -> BackfillChunk {
+        BackfillChunk {
+            indexer_id: 1,
+            from_block,
+            to_block,
+        }
+    }
+
+    #[test]
+    fn plans_chunks_aligned_to_chunk_size() {
+        assert_eq!(
+            plan_chunks(150, 420, 100, &[]),
+            vec![(150, 200), (200, 300), (300, 400), (400, 420)]
+        );
+        assert!(plan_chunks(420, 420, 100, &[]).is_empty());
+    }
+
+    #[test]
+    fn plans_chunks_skipping_completed_ones() {
+        let done = [completed(200, 300), completed(400, 450)];
+        assert_eq!(plan_chunks(100, 500, 100, &done), vec![(100, 200), (300, 400), (450, 500)]);
+    }
+
+    #[test]
+    fn plans_rest_of_chunks_completed_by_consecutive_ranges() {
+        // worker of an older run committed its chunk in several ranges
+        let done = [completed(100, 130), completed(130, 160)];
+        assert_eq!(plan_chunks(100, 300, 100, &done), vec![(160, 200), (200, 300)]);
+    }
+
+    #[test]
+    fn advances_only_over_contiguous_finished_chunks() {
+        let mut finished = vec![(300, 400), (200, 300)];
+        assert_eq!(take_contiguous_prefix(100, &mut finished), 100);
+        assert_eq!(finished.len(), 2);
+        finished.push((100, 200));
+        assert_eq!(take_contiguous_prefix(100, &mut finished), 400);
+        assert!(finished.is_empty());
+    }
+
+    #[test]
+    fn keeps_chunks_behind_a_gap() {
+        let mut finished = vec![(500, 600), (100, 200), (300, 400)];
+        assert_eq!(take_contiguous_prefix(100, &mut finished), 200);
+        finished.sort();
+        assert_eq!(finished, vec![(300, 400), (500, 600)]);
+    }
+}
@@ -0,0 +1,38 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const BASE_DELAY_MILLIS: u64 = 1_000;
+const MAX_DELAY_MILLIS: u64 = 300_000;
+
+/// Exponential backoff with full jitter for retrying transient cycle errors.
+pub struct Backoff {
+    attempt: u32,
+}
+
+impl Backoff {
+    pub fn new() -> Self {
+        Self { attempt: 0 }
+    }
+
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    pub fn next_delay(&mut self) -> Duration {
+        let exponential = BASE_DELAY_MILLIS.saturating_mul(1 << std::cmp::min(self.attempt, 16));
+        let capped = std::cmp::min(exponential, MAX_DELAY_MILLIS);
+        self.attempt += 1;
+        // half of the delay is fixed, the other half is random to spread out retries
+        Duration::from_millis(capped / 2 + jitter(capped / 2 + 1))
+    }
+
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
+
+fn jitter(upper_bound: u64) -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(now) => now.subsec_nanos() as u64 % upper_bound,
+        Err(_) => 0,
+    }
+}
@@ -1,10 +1,9 @@
-use rust_decimal::Decimal;
 use web3::types::{H256, H160, U256};
 
 use super::commons::IndexerError;
 
-pub fn hex_string_to_bytes32(hex_string: &String) -> Result<H256, IndexerError> {
-    let mut source = hex_string.clone();
+pub fn hex_string_to_bytes32(hex_string: &str) -> Result<H256, IndexerError> {
+    let mut source = hex_string.to_string();
     if source.starts_with("0x") {
         assert_eq!(source.remove(0), '0');
         assert_eq!(source.remove(0), 'x');
@@ -52,14 +51,11 @@ pub fn bytes32_to_uint256(value: &H256) -> Result<U256, IndexerError> {
 }
 
 pub fn bytes32_to_usize(value: &H256) -> Result<usize, IndexerError> {
-    match usize::from_str_radix(value.to_string().as_str(), 10) {
-        Ok(result) => {
-            Ok(result)
-        },
-        Err(e) => {
-            Err(IndexerError { reason: format!("During converting from string to u128 occurred {e}") })
-        }
+    let result = bytes32_to_uint256(value)?;
+    if result > U256::from(usize::MAX) {
+        return Err(IndexerError { reason: format!("During converting bytes32 to usize value {result} overflowed") });
     }
+    Ok(result.as_usize())
 }
 
 pub fn bytes32_to_string(value: &H256) -> String {
@@ -67,11 +63,19 @@ pub fn bytes32_to_string(value: &H256) -> String {
 }
 
 pub fn bytes_to_uint256_array(value: &[u8], location: usize) -> Result<Vec<U256>, IndexerError> {
+    if value.len() < location + 32 {
+        return Err(IndexerError { reason: format!("Array location {location} is out of {} bytes", value.len()) });
+    }
     let raw_length = &H256::from_slice(&value[location..location+32]);
-    let length = bytes32_to_usize(&raw_length)?;
+    let length = bytes32_to_usize(raw_length)?;
+    // array items follow its length word
+    let items_start = location + 32;
+    if value.len() < items_start + length * 32 {
+        return Err(IndexerError { reason: format!("Array of {length} items at {location} is out of {} bytes", value.len()) });
+    }
     let mut result = vec![];
-    for i in 1..length {
-        let raw_uint256_value = &value[i*32..(i+1)*32];
+    for i in 0..length {
+        let raw_uint256_value = &value[items_start + i*32..items_start + (i+1)*32];
         result.push(bytes32_to_uint256(&H256::from_slice(raw_uint256_value))?);
     }
     Ok(result)
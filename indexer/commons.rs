@@ -1,3 +1,16 @@
+#[derive(Debug)]
 pub struct IndexerError {
     pub reason: String,
 }
+
+/// Outcome of a failed cycle: transient errors are retried, fatal ones stop the indexer.
+pub enum CycleError {
+    Transient(IndexerError),
+    Fatal(IndexerError),
+}
+
+impl From<IndexerError> for CycleError {
+    fn from(value: IndexerError) -> Self {
+        Self::Transient(value)
+    }
+}
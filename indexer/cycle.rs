@@ -1,220 +1,559 @@
-use super::{commons::IndexerError, transactions::Transaction};
+use super::{
+    commons::{CycleError, IndexerError},
+    transactions::Transaction,
+};
 use crate::{
-    indexer::{event_parsers::get_event_parser, strategies::build_strategy},
-    models::{Indexer, Network, Token, TokenTransfer, TokenType},
+    indexer::{
+        adaptive_step::AdaptiveStep,
+        backoff::Backoff,
+        event_parsers::get_event_parser,
+        enrichment::Enricher,
+        fetcher::{fetch_ranges, get_block_number, route_logs_to_tokens, FetchedRange},
+        metrics,
+        reorg::{add_rollback_to_db_tx, find_common_ancestor, rollback, MAX_REORG_DEPTH},
+        strategies::build_strategy,
+        shutdown::ExitStatus,
+        subscription::HeadSubscription,
+        traces::get_trace_api,
+        transports::TransportPool,
+    },
+    init_db_client,
+    models::{migrate, BlockHash, Indexer, IndexerStatus, Network, Token, TokenTransfer},
 };
-use log::{info, warn};
-use postgres::Client;
-use std::{env, str::FromStr, thread, time::Duration};
-use tokio::runtime::Runtime;
-use web3::{
-    api::BaseFilter,
-    transports::Http,
-    types::{Address, FilterBuilder, Log, H256, U64},
-    Web3,
+use log::{error, info, warn};
+use std::time::{Duration, Instant};
+use tokio::sync::{
+    mpsc::{self, Receiver},
+    watch,
 };
+use tokio_postgres::Client;
+use web3::types::H256;
 
-pub fn start(client: &mut Client) {
-    match env::var("INDEXER_NAME") {
-        Ok(indexer_name) => {
-            initialize_indexer(client, indexer_name);
-        }
-        Err(e) => {
-            warn!("INDEXER_NAME: {}", e.to_string());
-        }
-    }
-}
+// fetched ranges waiting to be written, bounds memory when database is slower than RPC
+const PIPELINE_DEPTH: usize = 2;
 
-fn initialize_indexer(client: &mut Client, indexer_name: String) {
-    match Indexer::load_from_db(client, &indexer_name) {
+pub async fn start(client: &mut Client, indexer_name: &String, shutdown: watch::Receiver<bool>) -> ExitStatus {
+    if let Err(e) = migrate(client).await {
+        warn!("On migrating database occurred {}", e.reason);
+        return ExitStatus::Failed;
+    }
+    match Indexer::load_from_db(client, indexer_name).await {
         Ok(mut indexer) => {
             info!("Starting indexer {indexer}");
-            indexer_cycle(client, &mut indexer);
+            let transports = TransportPool::new();
+            indexer_cycle(client, &mut indexer, &transports, shutdown).await
         }
         Err(e) => {
             warn!("On instantiating indexer {} occurred", e.reason);
+            ExitStatus::Failed
         }
     }
 }
 
-fn indexer_cycle(client: &mut Client, indexer: &mut Indexer) {
-    let runtime = tokio::runtime::Runtime::new().unwrap();
-    loop {
-        match cycle_body(client, indexer, &runtime) {
-            Ok(_) => {
-                thread::sleep(Duration::from_secs(indexer.long_sleep_seconds as u64));
+/// Runs the indexer until it is stopped, finished or fails.
+/// Stop request is honoured between ranges, so a range is never left half written.
+pub async fn indexer_cycle(
+    client: &mut Client,
+    indexer: &mut Indexer,
+    transports: &TransportPool,
+    mut stop: watch::Receiver<bool>,
+) -> ExitStatus {
+    let mut step = AdaptiveStep::new();
+    let mut backoff = Backoff::new();
+    let mut subscription = HeadSubscription::new();
+    let mut enricher = Enricher::new();
+    while !*stop.borrow() {
+        match iteration(client, indexer, transports, &mut step, &mut subscription, &mut enricher, &stop).await {
+            Ok(Iteration::Indexed { reached_head }) => {
+                backoff.reset();
+                // catching up goes on quickly, waiting for new blocks only at the head
+                let (status, sleep_seconds) = if reached_head {
+                    (IndexerStatus::Synced, indexer.long_sleep_seconds)
+                } else {
+                    (IndexerStatus::Running, indexer.short_sleep_seconds)
+                };
+                report_status(client, indexer, status).await;
+                let sleep = sleep_unless_stopped(Duration::from_secs(sleep_seconds as u64), &mut stop);
+                if reached_head && subscription.is_live() {
+                    // new head wakes indexer up earlier, sleep stays as a fallback
+                    tokio::select! {
+                        _ = subscription.next_head() => {}
+                        _ = sleep => {}
+                    }
+                } else {
+                    sleep.await;
+                }
+            }
+            Ok(Iteration::Paused) => {
+                backoff.reset();
+                sleep_unless_stopped(Duration::from_secs(indexer.long_sleep_seconds as u64), &mut stop).await;
+            }
+            Ok(Iteration::Stopped) => {
+                info!("Indexer {} is stopped from database", indexer.name);
+                return ExitStatus::Completed;
+            }
+            Ok(Iteration::Finished) => {
+                report_status(client, indexer, IndexerStatus::Finished).await;
+                info!("Indexer {} reached its end block {}", indexer.name, indexer.last_block);
+                return ExitStatus::Completed;
             }
-            Err(e) => {
-                warn!("In cycle occurred error: {}", e.reason);
-                break;
+            Err(CycleError::Transient(e)) => {
+                report_status(client, indexer, IndexerStatus::Error).await;
+                let delay = backoff.next_delay();
+                warn!(
+                    "In cycle occurred error: {}. Retry #{} in {} ms",
+                    e.reason,
+                    backoff.attempt(),
+                    delay.as_millis()
+                );
+                sleep_unless_stopped(delay, &mut stop).await;
+                reconnect_if_closed(client).await;
+            }
+            Err(CycleError::Fatal(e)) => {
+                report_status(client, indexer, IndexerStatus::Error).await;
+                error!("In cycle occurred fatal error: {}. Stopping indexer {}", e.reason, indexer.name);
+                return ExitStatus::Failed;
             }
         }
     }
+    reconnect_if_closed(client).await;
+    report_status(client, indexer, IndexerStatus::Interrupted).await;
+    info!("Indexer {} interrupted at block {}", indexer.name, indexer.last_block);
+    ExitStatus::Completed
+}
+
+enum Iteration {
+    Indexed { reached_head: bool },
+    Paused,
+    Stopped,
+    Finished,
 }
 
-fn cycle_body(
+/// Reloads indexer from database and acts on the status operators set in Django admin.
+async fn iteration(
     client: &mut Client,
     indexer: &mut Indexer,
-    runtime: &Runtime,
-) -> Result<(), IndexerError> {
-    let network = get_network(client, indexer)?;
-    let transport = get_web3_transport(&network)?;
-    let web3 = web3::Web3::new(transport);
-    let range = get_blocks_range(
-        get_block_number(&web3, runtime)?,
-        indexer.last_block,
-        network.max_step,
-    );
-    info!("Fetching events from {} to {} blocks", range.0, range.1);
-    let strategy = build_strategy(indexer)?;
-    info!(
-        "Prepared strategy for fetching events: {}",
-        indexer.strategy.as_str()
-    );
-    let tokens = get_tokens(client, indexer)?;
+    transports: &TransportPool,
+    step: &mut AdaptiveStep,
+    subscription: &mut HeadSubscription,
+    enricher: &mut Enricher,
+    stop: &watch::Receiver<bool>,
+) -> Result<Iteration, CycleError> {
+    if let Err(e) = indexer.refresh(client).await {
+        return Err(CycleError::Transient(IndexerError {
+            reason: format!("During refreshing indexer occurred {}", e.reason),
+        }));
+    }
+    let status = match IndexerStatus::try_from(&indexer.status) {
+        Ok(status) => status,
+        Err(e) => {
+            warn!("{}, indexer {} is paused until it is corrected", e.reason, indexer.name);
+            IndexerStatus::Paused
+        }
+    };
+    match status {
+        IndexerStatus::Stopped => return Ok(Iteration::Stopped),
+        IndexerStatus::Finished => return Ok(Iteration::Finished),
+        IndexerStatus::Paused => return Ok(Iteration::Paused),
+        IndexerStatus::Reindex => reindex(client, indexer).await?,
+        IndexerStatus::Running | IndexerStatus::Synced | IndexerStatus::Error | IndexerStatus::Interrupted => {}
+    }
+    skip_to_start_block(client, indexer).await?;
+    if indexer.reached_end_block() {
+        return Ok(Iteration::Finished);
+    }
+    let reached_head = cycle_body(client, indexer, transports, step, subscription, enricher, stop).await?;
+    if indexer.reached_end_block() {
+        return Ok(Iteration::Finished);
+    }
+    Ok(Iteration::Indexed { reached_head })
+}
+
+/// Moves indexer which has not started yet to its start block, nothing before it is indexed.
+pub async fn skip_to_start_block(client: &mut Client, indexer: &mut Indexer) -> Result<(), IndexerError> {
+    match indexer.start_block {
+        Some(start_block) if start_block > indexer.last_block => {
+            info!("Indexer {} skips to its start block {start_block}", indexer.name);
+            update_last_block(client, indexer, start_block).await
+        }
+        _ => Ok(()),
+    }
+}
+
+async fn reindex(client: &mut Client, indexer: &mut Indexer) -> Result<(), CycleError> {
+    let Some(reindex_block) = indexer.reindex_block else {
+        return Err(CycleError::Fatal(IndexerError {
+            reason: String::from("Indexer status is reindex but no reindex_block requested"),
+        }));
+    };
+    // rolling back to a block ahead would skip every block in between
+    let reindex_block = if reindex_block > indexer.last_block {
+        warn!(
+            "Indexer {} is asked to reindex from block {reindex_block} which it has not reached, reindexing from its last block {}",
+            indexer.name, indexer.last_block
+        );
+        indexer.last_block
+    } else {
+        reindex_block
+    };
+    info!("Reindexing {} from block {reindex_block}", indexer.name);
+    // rollback and finished request are committed together, so a crash never replays the rollback
+    let mut db_tx = match client.transaction().await {
+        Ok(db_tx) => db_tx,
+        Err(e) => {
+            return Err(CycleError::Transient(IndexerError {
+                reason: format!("During preparing reindex transaction occurred {e}"),
+            }))
+        }
+    };
+    let deleted = add_rollback_to_db_tx(&mut db_tx, indexer, reindex_block).await?;
+    if let Err(e) = indexer.add_finish_reindex_to_db_tx(&mut db_tx).await {
+        return Err(CycleError::Transient(IndexerError {
+            reason: format!("During finishing reindex occurred {}", e.reason),
+        }));
+    }
+    if let Err(e) = db_tx.commit().await {
+        return Err(CycleError::Transient(IndexerError {
+            reason: format!("During commit of reindex occurred {e}"),
+        }));
+    }
     info!(
-        "Found {} tokens which are monitored by indexer",
-        tokens.len()
+        "Rolled back indexer {} from block {} to {reindex_block}, deleted {deleted} token transfers",
+        indexer.name, indexer.last_block
     );
-    let three_payload_topics = strategy.get_payload_topics(indexer.strategy_params.clone())?;
-    for token in tokens.iter() {
-        let events = token.token_type.get_events_hashes();
-        info!(
-            "Token {} has {} event type to handle",
-            token.name.as_str(),
-            events.len()
-        );
-        for event in events.iter() {
-            let topics = [
-                Some(vec![*event]),
-                three_payload_topics[0].clone(),
-                three_payload_topics[1].clone(),
-                three_payload_topics[2].clone(),
-            ];
-            let filter = get_filter(&web3, runtime, token, range, &topics)?;
-            let logs = get_logs(runtime, filter)?;
-            let event_parser = get_event_parser(token);
-            info!(
-                "Fetched {} events for token {}",
-                logs.len(),
-                token.name.as_str()
-            );
-            let mut transactions = vec![];
-            for log in logs.iter() {
-                let transaction = event_parser.parse(log)?;
-                info!("{transaction}");
-                transactions.push(transaction);
+    indexer.last_block = reindex_block;
+    indexer.status = IndexerStatus::Running.to_string();
+    indexer.reindex_block = None;
+    Ok(())
+}
+
+async fn report_status(client: &mut Client, indexer: &mut Indexer, status: IndexerStatus) {
+    if IndexerStatus::try_from(&indexer.status).is_ok_and(|current| current == status) {
+        return;
+    }
+    if let Err(e) = indexer.update_status(client, status).await {
+        warn!("During updating status of indexer {} occurred {}", indexer.name, e.reason);
+    }
+}
+
+async fn sleep_unless_stopped(duration: Duration, stop: &mut watch::Receiver<bool>) {
+    let sleep = tokio::time::sleep(duration);
+    tokio::pin!(sleep);
+    loop {
+        tokio::select! {
+            _ = &mut sleep => return,
+            changed = stop.changed() => {
+                // nobody can stop indexer anymore, so just sleep out
+                if changed.is_err() {
+                    sleep.await;
+                    return;
+                }
+                if *stop.borrow() {
+                    return;
+                }
             }
-            info!("Saving to database {} token transfers", transactions.len());
-            save_token_transfers(client, indexer, token, transactions)?;
         }
     }
-    info!("Move indexer to block {}", range.1);
-    update_last_block(client, indexer, range.1)?;
-    Ok(())
 }
 
-fn get_network(client: &mut Client, indexer: &mut Indexer) -> Result<Network, IndexerError> {
-    match Network::load_from_db(client, indexer.network_id) {
-        Ok(network) => {
-            info!("Network initialized {}", network.name.as_str());
-            Ok(network)
+pub async fn reconnect_if_closed(client: &mut Client) {
+    if !client.is_closed() {
+        return;
+    }
+    match init_db_client().await {
+        Ok(new_client) => {
+            info!("Database connection was lost and is reestablished");
+            *client = new_client;
         }
-        Err(e) => Err(IndexerError {
-            reason: format!("When fetching network occurred: {}", e.reason),
-        }),
+        Err(e) => warn!("On reconnecting to database occurred {e}"),
     }
 }
 
-fn get_web3_transport(network: &Network) -> Result<Http, IndexerError> {
-    match web3::transports::Http::new(network.rpc_url.as_str()) {
-        Ok(transport) => Ok(transport),
-        Err(e) => Err(IndexerError {
-            reason: e.to_string(),
-        }),
+/// Fetches ranges up to the confirmed head while already fetched ones are written,
+/// and returns whether indexer reached the head.
+async fn cycle_body(
+    client: &mut Client,
+    indexer: &mut Indexer,
+    transports: &TransportPool,
+    step: &mut AdaptiveStep,
+    subscription: &mut HeadSubscription,
+    enricher: &mut Enricher,
+    stop: &watch::Receiver<bool>,
+) -> Result<bool, CycleError> {
+    let network = get_network(client, indexer).await?;
+    enricher.configure(&network, indexer);
+    let transport = transports.get(&network).await?;
+    transport.cross_check_heads().await;
+    let web3 = web3::Web3::new(transport.clone());
+    if let Some(ancestor) = find_common_ancestor(client, indexer, &web3).await? {
+        warn!("Chain reorganization detected, common ancestor is block {ancestor}");
+        rollback(client, indexer, ancestor).await?;
     }
+    let three_payload_topics = get_payload_topics(indexer)?;
+    let tokens = get_tokens(client, indexer).await?;
+    info!(
+        "Found {} tokens which are monitored by indexer",
+        tokens.len()
+    );
+    let head = match get_block_number(&web3, &network).await {
+        Ok(head) => head,
+        Err(e) => {
+            // transport is kept with its endpoint statistics, failed WebSockets reconnect on next call
+            warn!("No endpoint returned head block: {}", transport.health_report());
+            return Err(CycleError::Transient(e));
+        }
+    };
+    subscription.ensure_subscribed(&transport).await;
+    metrics::set_progress(&indexer.name, &network.name, Some(head), indexer.last_block);
+    // bounded indexer does not follow the chain past its end block
+    let head = indexer.end_block.map_or(head, |end_block| std::cmp::min(head, end_block));
+    let (sender, receiver) = mpsc::channel(PIPELINE_DEPTH);
+    let from_block = indexer.last_block;
+    let (_, written) = tokio::join!(
+        fetch_ranges(
+            &web3,
+            &tokens,
+            &three_payload_topics,
+            from_block,
+            head,
+            network.max_step,
+            step,
+            enricher,
+            get_trace_api(&network, indexer),
+            stop,
+            sender,
+        ),
+        write_ranges(client, indexer, &network.name, &tokens, head, receiver),
+    );
+    metrics::set_gauge(
+        "evm_indexer_step_blocks",
+        &[("indexer", &indexer.name), ("network", &network.name)],
+        step.current(network.max_step) as f64,
+    );
+    written
 }
 
-fn get_block_number(web3: &Web3<Http>, runtime: &Runtime) -> Result<u64, IndexerError> {
-    match runtime.block_on(web3.eth().block_number()) {
-        Ok(number) => Ok(number.as_u64()),
-        Err(e) => Err(IndexerError {
-            reason: e.to_string(),
-        }),
+async fn write_ranges(
+    client: &mut Client,
+    indexer: &mut Indexer,
+    network_name: &str,
+    tokens: &[Token],
+    head: u64,
+    mut receiver: Receiver<Result<FetchedRange, CycleError>>,
+) -> Result<bool, CycleError> {
+    let mut reached_head = false;
+    while let Some(fetched) = receiver.recv().await {
+        let fetched = fetched?;
+        let range = fetched.range;
+        write_range(client, indexer, network_name, tokens, fetched).await?;
+        reached_head = range.1 >= head;
     }
+    Ok(reached_head)
 }
 
-fn get_blocks_range(latest_block_blockchain: u64, latest_block_db: u64, step: u64) -> (u64, u64) {
-    return (
-        latest_block_db,
-        std::cmp::min(latest_block_blockchain, latest_block_db + step),
+async fn write_range(
+    client: &mut Client,
+    indexer: &mut Indexer,
+    network_name: &str,
+    tokens: &[Token],
+    fetched: FetchedRange,
+) -> Result<(), CycleError> {
+    let range = fetched.range;
+    let mut block_hashes = vec![BlockHash {
+        indexer_id: indexer.id,
+        block_number: range.1,
+        block_hash: fetched.boundary_hash.clone(),
+    }];
+    let mut token_transfers = vec![];
+    let mut token_counts = vec![];
+    let parsed = parse_range(&indexer.name, network_name, tokens, &fetched);
+    for (token, transactions) in parsed {
+        let saved_before = token_transfers.len();
+        for transaction in transactions.iter() {
+            block_hashes.push(BlockHash {
+                indexer_id: indexer.id,
+                block_number: transaction.block_number,
+                block_hash: transaction.block_hash.clone(),
+            });
+            token_transfers.extend(TokenTransfer::build_from_transaction(transaction, token, indexer));
+        }
+        token_counts.push((token, transactions.len(), token_transfers.len() - saved_before));
+    }
+    info!(
+        "Saving to database {} token transfers and moving indexer to block {}",
+        token_transfers.len(),
+        range.1
+    );
+    let started = Instant::now();
+    commit_range(client, indexer, token_transfers, block_hashes, range.1).await?;
+    metrics::observe(
+        "evm_indexer_db_commit_duration_seconds",
+        &[("indexer", &indexer.name), ("network", network_name)],
+        started.elapsed(),
+    );
+    metrics::set_progress(&indexer.name, network_name, None, range.1);
+    metrics::inc_counter(
+        "evm_indexer_ranges_processed_total",
+        &[("indexer", &indexer.name), ("network", network_name)],
+        1,
     );
+    for (token, fetched_count, saved_count) in token_counts {
+        let labels = [("indexer", indexer.name.as_str()), ("network", network_name), ("token", token.name.as_str())];
+        metrics::inc_counter("evm_indexer_logs_fetched_total", &labels, fetched_count as u64);
+        metrics::inc_counter("evm_indexer_transfers_saved_total", &labels, saved_count as u64);
+    }
+    Ok(())
 }
 
-fn get_filter(
-    web3: &Web3<Http>,
-    runtime: &Runtime,
-    token: &Token,
-    block_range: (u64, u64),
-    topics: &[Option<Vec<H256>>; 4],
-) -> Result<BaseFilter<Http, Log>, IndexerError> {
-    let filter_config = FilterBuilder::default()
-        .address(vec![Address::from_str(token.address.as_str()).unwrap()])
-        .from_block(web3::types::BlockNumber::Number(U64::from(block_range.0)))
-        .to_block(web3::types::BlockNumber::Number(U64::from(block_range.1)))
-        .topics(
-            topics[0].clone(),
-            topics[1].clone(),
-            topics[2].clone(),
-            topics[3].clone(),
-        )
-        .build();
-    match runtime.block_on(web3.eth_filter().create_logs_filter(filter_config)) {
-        Ok(base_filter) => Ok(base_filter),
-        Err(e) => Err(IndexerError {
-            reason: format!("During establishing new filter {e} occurred"),
-        }),
+/// Writes transfers of a range, its block hashes and the new last block in one database transaction,
+/// so after a crash the range is either fully indexed or indexed again from scratch.
+async fn commit_range(
+    client: &mut Client,
+    indexer: &mut Indexer,
+    token_transfers: Vec<TokenTransfer>,
+    block_hashes: Vec<BlockHash>,
+    last_block: u64,
+) -> Result<(), IndexerError> {
+    let mut db_tx = match client.transaction().await {
+        Ok(db_tx) => db_tx,
+        Err(e) => {
+            return Err(IndexerError {
+                reason: format!("During preparing database transaction occurred {e}"),
+            })
+        }
+    };
+    for token_transfer in token_transfers.iter() {
+        if let Err(e) = token_transfer.add_to_db_tx(&mut db_tx).await {
+            return Err(IndexerError {
+                reason: format!("Error occurred on token transfer saving: {}", e.reason),
+            });
+        }
+    }
+    for block_hash in block_hashes.iter() {
+        if let Err(e) = block_hash.add_to_db_tx(&mut db_tx).await {
+            return Err(IndexerError { reason: e.reason });
+        }
+    }
+    let prune_before = last_block.saturating_sub(MAX_REORG_DEPTH);
+    if let Err(e) = BlockHash::delete_before_block(&mut db_tx, indexer, prune_before).await {
+        return Err(IndexerError { reason: e.reason });
+    }
+    if let Err(e) = indexer.add_last_block_to_db_tx(&mut db_tx, last_block).await {
+        return Err(IndexerError {
+            reason: format!(
+                "During updating last block of indexer {} to {} occurred error {}",
+                indexer.name.as_str(),
+                last_block,
+                e.reason
+            ),
+        });
+    }
+    if let Err(e) = db_tx.commit().await {
+        return Err(IndexerError {
+            reason: format!("During commit of range up to block {last_block} occurred {e}"),
+        });
     }
+    indexer.last_block = last_block;
+    Ok(())
 }
 
-fn get_tokens(client: &mut Client, indexer: &Indexer) -> Result<Vec<Token>, IndexerError> {
-    match Token::load_tokens_from_db_by_indexer(client, indexer) {
-        Ok(tokens) => Ok(tokens),
-        Err(e) => Err(IndexerError {
-            reason: format!("During fetching tokens {} occurred", e.reason),
-        }),
+/// Topics of the indexer strategy, errors here are fatal as they come from configuration.
+pub fn get_payload_topics(indexer: &Indexer) -> Result<[Option<Vec<H256>>; 3], CycleError> {
+    let strategy = build_strategy(indexer).map_err(CycleError::Fatal)?;
+    info!(
+        "Prepared strategy for fetching events: {}",
+        indexer.strategy.as_str()
+    );
+    strategy
+        .get_payload_topics(indexer.strategy_params.clone())
+        .map_err(CycleError::Fatal)
+}
+
+/// Parses events of the range and turns its native transfers into transactions of native tokens.
+/// Any contract may emit a malformed event under a watched signature, so such events are skipped and counted.
+pub fn parse_range<'a>(
+    indexer_name: &str,
+    network_name: &str,
+    tokens: &'a [Token],
+    fetched: &FetchedRange,
+) -> Vec<(&'a Token, Vec<Transaction>)> {
+    let enrichment = &fetched.enrichment;
+    let mut parsed = vec![];
+    for (token, token_logs) in route_logs_to_tokens(tokens, &fetched.logs) {
+        if token.token_type.is_native() {
+            let mut transactions = vec![];
+            for transfer in fetched.native_transfers.iter() {
+                let mut transaction = transfer.to_transaction(token);
+                enrichment.apply(&mut transaction);
+                info!("{transaction}");
+                transactions.push(transaction);
+            }
+            info!("Fetched {} native transfers for token {}", transactions.len(), token.name.as_str());
+            parsed.push((token, transactions));
+            continue;
+        }
+        let event_parser = get_event_parser(token);
+        info!(
+            "Fetched {} events for token {}",
+            token_logs.len(),
+            token.name.as_str()
+        );
+        let mut transactions = vec![];
+        for log in token_logs.iter() {
+            let mut transaction = match event_parser.parse(log) {
+                Ok(transaction) => transaction,
+                Err(e) => {
+                    warn!(
+                        "Skipping event {:?} #{:?} of token {}: {}",
+                        log.transaction_hash,
+                        log.log_index,
+                        token.name.as_str(),
+                        e.reason
+                    );
+                    metrics::inc_counter(
+                        "evm_indexer_logs_skipped_total",
+                        &[("indexer", indexer_name), ("network", network_name), ("token", token.name.as_str())],
+                        1,
+                    );
+                    continue;
+                }
+            };
+            enrichment.apply(&mut transaction);
+            info!("{transaction}");
+            transactions.push(transaction);
+        }
+        parsed.push((token, transactions));
     }
+    parsed
 }
 
-fn get_logs(runtime: &Runtime, filter: BaseFilter<Http, Log>) -> Result<Vec<Log>, IndexerError> {
-    match runtime.block_on(filter.logs()) {
-        Ok(logs) => Ok(logs),
+pub async fn get_network(client: &mut Client, indexer: &mut Indexer) -> Result<Network, IndexerError> {
+    match Network::load_from_db(client, indexer.network_id).await {
+        Ok(network) => {
+            info!("Network initialized {}", network.name.as_str());
+            Ok(network)
+        }
         Err(e) => Err(IndexerError {
-            reason: format!("Error occurred on logs fetching {e}"),
+            reason: format!("When fetching network occurred: {}", e.reason),
         }),
     }
 }
 
-fn save_token_transfers(
-    client: &mut Client,
-    indexer: &Indexer,
-    token: &Token,
-    transactions: Vec<Transaction>,
-) -> Result<(), IndexerError> {
-    match TokenTransfer::save_many(client, transactions, token, indexer) {
-        Ok(()) => Ok(()),
+pub async fn get_tokens(client: &mut Client, indexer: &Indexer) -> Result<Vec<Token>, IndexerError> {
+    match Token::load_tokens_from_db_by_indexer(client, indexer).await {
+        Ok(tokens) => Ok(tokens),
         Err(e) => Err(IndexerError {
-            reason: format!("Error occurred on token transfer saving: {}", e.reason),
+            reason: format!("During fetching tokens {} occurred", e.reason),
         }),
     }
 }
 
-fn update_last_block(
+async fn update_last_block(
     client: &mut Client,
     indexer: &mut Indexer,
     last_block: u64,
 ) -> Result<(), IndexerError> {
-    match indexer.update_last_block(client, last_block) {
+    match indexer.update_last_block(client, last_block).await {
         Ok(()) => Ok(()),
         Err(e) => Err(IndexerError {
             reason: format!(
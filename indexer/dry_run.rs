@@ -0,0 +1,161 @@
+use log::{error, info, warn};
+use tokio::sync::watch;
+use tokio_postgres::Client;
+use web3::Web3;
+
+use crate::models::{Indexer, Token};
+
+use super::{
+    adaptive_step::AdaptiveStep,
+    commons::{CycleError, IndexerError},
+    cycle::{get_network, get_payload_topics, get_tokens},
+    enrichment::Enricher,
+    event_parsers::get_event_parser,
+    fetcher::{fetch_range, get_block_number, route_logs_to_tokens, FetchedRange},
+    shutdown::ExitStatus,
+    traces::get_trace_api,
+    transactions::Transaction,
+    transports::TransportPool,
+};
+
+/// What one token would get stored over the range.
+struct TokenReport {
+    name: String,
+    transactions: usize,
+    parse_errors: usize,
+}
+
+/// Runs fetching and parsing of the indexer over `from_block..=to_block`
+/// and prints transactions to stdout as JSON lines. Nothing is written to the database.
+/// Range starts at the indexer last block and spans one step by default.
+pub async fn dry_run(
+    client: &mut Client,
+    indexer_name: &String,
+    from_block: Option<u64>,
+    to_block: Option<u64>,
+    shutdown: watch::Receiver<bool>,
+) -> ExitStatus {
+    let mut indexer = match Indexer::load_from_db(client, indexer_name).await {
+        Ok(indexer) => indexer,
+        Err(e) => {
+            warn!("On instantiating indexer {} occurred", e.reason);
+            return ExitStatus::Failed;
+        }
+    };
+    match run_dry_run(client, &mut indexer, from_block, to_block, shutdown).await {
+        Ok(reports) => {
+            let mut parse_errors = 0;
+            for report in reports.iter() {
+                info!(
+                    "Token {}: {} transactions, {} parse errors",
+                    report.name, report.transactions, report.parse_errors
+                );
+                parse_errors += report.parse_errors;
+            }
+            let transactions: usize = reports.iter().map(|report| report.transactions).sum();
+            info!("Dry run of {} produced {transactions} transactions and {parse_errors} parse errors", indexer.name);
+            if parse_errors > 0 {
+                ExitStatus::Failed
+            } else {
+                ExitStatus::Completed
+            }
+        }
+        Err(e) => {
+            error!("Dry run of {} failed: {}", indexer.name, e.reason);
+            ExitStatus::Failed
+        }
+    }
+}
+
+async fn run_dry_run(
+    client: &mut Client,
+    indexer: &mut Indexer,
+    from_block: Option<u64>,
+    to_block: Option<u64>,
+    shutdown: watch::Receiver<bool>,
+) -> Result<Vec<TokenReport>, IndexerError> {
+    let network = get_network(client, indexer).await?;
+    let tokens = get_tokens(client, indexer).await?;
+    let three_payload_topics = match get_payload_topics(indexer) {
+        Ok(topics) => topics,
+        Err(CycleError::Transient(e)) | Err(CycleError::Fatal(e)) => return Err(e),
+    };
+    let transports = TransportPool::new();
+    let web3 = Web3::new(transports.get(&network).await?);
+    let from_block = from_block.unwrap_or(indexer.last_block);
+    let to_block = match to_block {
+        Some(to_block) => to_block,
+        None => std::cmp::min(from_block + network.max_step, get_block_number(&web3, &network).await?),
+    };
+    if to_block < from_block {
+        return Err(IndexerError {
+            reason: format!("Dry run range {from_block} to {to_block} is empty"),
+        });
+    }
+    info!("Dry run of {} from block {from_block} to {to_block}", indexer.name);
+    let mut reports: Vec<TokenReport> = tokens
+        .iter()
+        .map(|token| TokenReport {
+            name: token.name.clone(),
+            transactions: 0,
+            parse_errors: 0,
+        })
+        .collect();
+    let mut step = AdaptiveStep::new();
+    let mut enricher = Enricher::new();
+    enricher.configure(&network, indexer);
+    let mut from_block = from_block;
+    while !*shutdown.borrow() {
+        let fetched = match fetch_range(
+            &web3,
+            &tokens,
+            &three_payload_topics,
+            from_block,
+            to_block,
+            network.max_step,
+            &mut step,
+            &mut enricher,
+            get_trace_api(&network, indexer),
+        )
+        .await
+        {
+            Ok(fetched) => fetched,
+            Err(CycleError::Transient(e)) | Err(CycleError::Fatal(e)) => return Err(e),
+        };
+        print_range(&tokens, &fetched, &mut reports);
+        if fetched.range.1 >= to_block {
+            break;
+        }
+        // unlike indexing, boundary block is not fetched twice so nothing is printed twice
+        from_block = fetched.range.1 + 1;
+    }
+    Ok(reports)
+}
+
+/// Parses every event on its own, so one bad event shows up as an error instead of hiding the rest.
+fn print_range(tokens: &[Token], fetched: &FetchedRange, reports: &mut [TokenReport]) {
+    for (i, (token, token_logs)) in route_logs_to_tokens(tokens, &fetched.logs).into_iter().enumerate() {
+        let mut results: Vec<Result<Transaction, IndexerError>> = vec![];
+        if token.token_type.is_native() {
+            results.extend(fetched.native_transfers.iter().map(|transfer| Ok(transfer.to_transaction(token))));
+        } else {
+            let event_parser = get_event_parser(token);
+            results.extend(token_logs.iter().map(|log| event_parser.parse(log)));
+        }
+        for result in results {
+            match result {
+                Ok(mut transaction) => {
+                    fetched.enrichment.apply(&mut transaction);
+                    let mut line = transaction.to_json();
+                    line["token"] = serde_json::Value::String(token.name.clone());
+                    println!("{line}");
+                    reports[i].transactions += 1;
+                }
+                Err(e) => {
+                    error!("Token {} has event which failed to parse: {}", token.name, e.reason);
+                    reports[i].parse_errors += 1;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::{HashMap, HashSet};
+
+use web3::{
+    types::{Log, H256},
+    Web3,
+};
+
+use crate::models::{Indexer, Network};
+
+use super::{
+    byte_parsers::bytes32_to_string,
+    commons::IndexerError,
+    headers::HeaderCache,
+    native::NativeTransfer,
+    receipts::fetch_receipt_details,
+    transactions::{ReceiptDetails, Transaction},
+    transports::RpcTransport,
+};
+
+/// Data about the blocks and transactions behind logs of a range, fetched along with the logs.
+#[derive(Default)]
+pub struct Enrichment {
+    timestamps: HashMap<u64, u64>,
+    receipts: HashMap<String, ReceiptDetails>,
+}
+
+impl Enrichment {
+    /// Fills parsed transaction with what was fetched for its block and tx hash.
+    pub fn apply(&self, transaction: &mut Transaction) {
+        if transaction.block_timestamp.is_none() {
+            transaction.block_timestamp = self.timestamps.get(&transaction.block_number).copied();
+        }
+        transaction.receipt = self.receipts.get(&transaction.tx_hash).cloned();
+    }
+}
+
+pub struct Enricher {
+    headers: HeaderCache,
+    fetch_receipts: bool,
+}
+
+impl Enricher {
+    pub fn new() -> Self {
+        Self {
+            headers: HeaderCache::new(),
+            fetch_receipts: false,
+        }
+    }
+
+    pub fn configure(&mut self, network: &Network, indexer: &Indexer) {
+        self.headers.set_need_poa(network.need_poa);
+        self.fetch_receipts = indexer.enrich_transactions;
+    }
+
+    pub async fn enrich(
+        &mut self,
+        web3: &Web3<RpcTransport>,
+        logs: &[Log],
+        native_transfers: &[NativeTransfer],
+    ) -> Result<Enrichment, IndexerError> {
+        let mut blocks = vec![];
+        let mut tx_hashes: Vec<H256> = vec![];
+        // ranges of backfill chunks hold tens of thousands of logs, so seen hashes are looked up in a set
+        let mut seen_tx_hashes: HashSet<H256> = HashSet::new();
+        for log in logs.iter() {
+            if let (Some(block_number), Some(block_hash)) = (log.block_number, log.block_hash) {
+                blocks.push((block_number.as_u64(), bytes32_to_string(&block_hash)));
+            }
+            // one receipt serves every transfer of the transaction
+            if let Some(tx_hash) = log.transaction_hash {
+                if seen_tx_hashes.insert(tx_hash) {
+                    tx_hashes.push(tx_hash);
+                }
+            }
+        }
+        // native transfers come with their block timestamp, only receipts are missing
+        for transfer in native_transfers.iter() {
+            if let Ok(tx_hash) = transfer.tx_hash.parse::<H256>() {
+                if seen_tx_hashes.insert(tx_hash) {
+                    tx_hashes.push(tx_hash);
+                }
+            }
+        }
+        let timestamps = self.headers.get_timestamps(web3, &blocks).await?;
+        let receipts = if self.fetch_receipts {
+            fetch_receipt_details(web3, &tx_hashes).await?
+        } else {
+            HashMap::new()
+        };
+        Ok(Enrichment { timestamps, receipts })
+    }
+}
@@ -26,13 +26,33 @@ fn get_event_address(event: &Log, target_token: &Token) -> Result<String, Indexe
 }
 
 fn get_event_tx_hash(event: &Log) -> Result<String, IndexerError> {
-    if event.transaction_hash.is_none() {
-        Err(IndexerError {
+    match &event.transaction_hash {
+        Some(transaction_hash) => Ok(bytes32_to_string(transaction_hash)),
+        None => Err(IndexerError {
             reason: String::from("Event has no tx hash. abort"),
-        })
-    } else {
-        let transaction_hash = bytes32_to_string(&event.transaction_hash.unwrap());
-        Ok(transaction_hash)
+        }),
+    }
+}
+
+fn get_event_block(event: &Log) -> Result<(u64, String), IndexerError> {
+    match (event.block_number, event.block_hash) {
+        (Some(block_number), Some(block_hash)) => {
+            Ok((block_number.as_u64(), bytes32_to_string(&block_hash)))
+        }
+        _ => Err(IndexerError {
+            reason: String::from("Event has no block number or block hash. abort"),
+        }),
+    }
+}
+
+fn get_event_position(event: &Log) -> Result<(u64, u64), IndexerError> {
+    match (event.log_index, event.transaction_index) {
+        (Some(log_index), Some(transaction_index)) => {
+            Ok((log_index.as_u64(), transaction_index.as_u64()))
+        }
+        _ => Err(IndexerError {
+            reason: String::from("Event has no log index or transaction index. abort"),
+        }),
     }
 }
 
@@ -48,11 +68,11 @@ fn get_event_topics_length(event: &Log) -> Result<usize, IndexerError> {
             reason: format!("Bad event. Expected 3 or 4 topics, actual {length}"),
         });
     }
-    return Ok(length);
+    Ok(length)
 }
 
 fn get_event_signature(event: &Log, target_token: &Token) -> Result<H256, IndexerError> {
-    let topic = event.topics.get(0).unwrap();
+    let topic = event.topics.first().unwrap();
     let target_signatures = target_token.token_type.get_events_hashes();
     if target_signatures.contains(topic) {
         Ok(*topic)
@@ -62,31 +82,39 @@ fn get_event_signature(event: &Log, target_token: &Token) -> Result<H256, Indexe
 }
 
 fn get_event_participants(event: &Log) -> Result<(String, String), IndexerError> {
-    let sender;
-    match event.topics.get(1) {
-        Some(raw_sender) => {
-            sender = bytes32_to_address(raw_sender);
-        }
+    let sender = match event.topics.get(1) {
+        Some(raw_sender) => bytes32_to_address(raw_sender),
         None => {
             return Err(IndexerError {
-                reason: format!("Sender not found"),
+                reason: String::from("Sender not found"),
             });
         }
-    }
-    let recipient;
-    match event.topics.get(2) {
-        Some(raw_recipient) => {
-            recipient = bytes32_to_address(raw_recipient);
-        }
+    };
+    let recipient = match event.topics.get(2) {
+        Some(raw_recipient) => bytes32_to_address(raw_recipient),
         None => {
             return Err(IndexerError {
-                reason: format!("Recipient not found"),
+                reason: String::from("Recipient not found"),
             });
         }
-    }
+    };
     Ok((sender, recipient))
 }
 
+/// ERC1155 events index operator, sender and recipient, in this order.
+fn get_erc1155_participants(event: &Log) -> Result<(String, String, String), IndexerError> {
+    let (operator, sender) = get_event_participants(event)?;
+    let recipient = match event.topics.get(3) {
+        Some(raw_recipient) => bytes32_to_address(raw_recipient),
+        None => {
+            return Err(IndexerError {
+                reason: String::from("Recipient not found"),
+            });
+        }
+    };
+    Ok((operator, sender, recipient))
+}
+
 pub struct FungibleEventParser<'a> {
     target_token: &'a Token,
 }
@@ -95,8 +123,10 @@ impl<'a> EventParser for FungibleEventParser<'a> {
     fn parse(&self, event: &Log) -> Result<Transaction, IndexerError> {
         let address = get_event_address(event, self.target_token)?;
         let tx_hash = get_event_tx_hash(event)?;
+        let (block_number, block_hash) = get_event_block(event)?;
+        let (log_index, transaction_index) = get_event_position(event)?;
         let topics_count = get_event_topics_length(event)?;
-        let event_signature = get_event_signature(event, self.target_token)?;
+        get_event_signature(event, self.target_token)?;
         let (sender, recipient) = get_event_participants(event)?;
         let source_for_amount: H256; // if amount is indexed then it is in topics; otherwise in event data
         if topics_count == 3 && event.data.0.len() == 32 {
@@ -112,6 +142,13 @@ impl<'a> EventParser for FungibleEventParser<'a> {
             sender,
             recipient,
             tx_hash,
+            block_number,
+            block_hash,
+            log_index,
+            transaction_index,
+            operator: None,
+            block_timestamp: None,
+            receipt: None,
             transferred_token: TransferredToken::Fungible { amount, address },
         })
     }
@@ -125,8 +162,10 @@ impl<'a> EventParser for NFTEventParser<'a> {
     fn parse(&self, event: &Log) -> Result<Transaction, IndexerError> {
         let address = get_event_address(event, self.target_token)?;
         let tx_hash = get_event_tx_hash(event)?;
+        let (block_number, block_hash) = get_event_block(event)?;
+        let (log_index, transaction_index) = get_event_position(event)?;
         let topics_count = get_event_topics_length(event)?;
-        let event_signature = get_event_signature(event, self.target_token)?;
+        get_event_signature(event, self.target_token)?;
         let (sender, recipient) = get_event_participants(event)?;
         let source_for_token_id: H256; // if amount is indexed then it is in topics; otherwise in event data
         if topics_count == 3 && event.data.0.len() == 32 {
@@ -142,6 +181,13 @@ impl<'a> EventParser for NFTEventParser<'a> {
             sender,
             recipient,
             tx_hash,
+            block_number,
+            block_hash,
+            log_index,
+            transaction_index,
+            operator: None,
+            block_timestamp: None,
+            receipt: None,
             transferred_token: TransferredToken::NFT { address, token_id },
         })
     }
@@ -155,8 +201,10 @@ impl<'a> EventParser for ERC1155EventParser<'a> {
     fn parse(&self, event: &Log) -> Result<Transaction, IndexerError> {
         let address = get_event_address(event, self.target_token)?;
         let tx_hash = get_event_tx_hash(event)?;
-        let topics_count = get_event_topics_length(event)?;
-        let (sender, recipient) = get_event_participants(event)?;
+        let (block_number, block_hash) = get_event_block(event)?;
+        let (log_index, transaction_index) = get_event_position(event)?;
+        get_event_topics_length(event)?;
+        let (operator, sender, recipient) = get_erc1155_participants(event)?;
         let event_signature = get_event_signature(event, self.target_token)?;
         let data = event.data.0.as_slice();
         if event_signature.as_bytes() == web3::signing::keccak256(ERC1155_TRANSFER_SINGLE) {
@@ -176,6 +224,13 @@ impl<'a> EventParser for ERC1155EventParser<'a> {
                 sender,
                 recipient,
                 tx_hash,
+                block_number,
+                block_hash,
+                log_index,
+                transaction_index,
+                operator: Some(operator),
+                block_timestamp: None,
+                receipt: None,
                 transferred_token: TransferredToken::ERC1155 {
                     address,
                     token_ids: vec![token_id],
@@ -183,10 +238,10 @@ impl<'a> EventParser for ERC1155EventParser<'a> {
                 },
             })
         } else if event_signature.as_bytes() == web3::signing::keccak256(ERC1155_TRANSFER_BATCH) {
-            if data.len() < 64 || data.len() % 32 != 0 {
+            if data.len() < 64 || !data.len().is_multiple_of(32) {
                 return Err(IndexerError {
                     reason: format!(
-                        "ERC1155 TransferSingle at {tx_hash} expected at least 64 bytes for data, found {}",
+                        "ERC1155 TransferBatch at {tx_hash} expected at least 64 bytes for data, found {}",
                         data.len()
                     ),
                 });
@@ -197,13 +252,34 @@ impl<'a> EventParser for ERC1155EventParser<'a> {
             let amounts_location =  bytes32_to_usize(&amounts_location_raw)?;
             let token_ids = bytes_to_uint256_array(data, token_ids_location)?;
             let amounts = bytes_to_uint256_array(data, amounts_location)?;
-            Ok(Transaction { sender, recipient, tx_hash, transferred_token: TransferredToken::ERC1155 { address, token_ids, amounts } })
+            if token_ids.len() != amounts.len() {
+                return Err(IndexerError {
+                    reason: format!(
+                        "ERC1155 TransferBatch at {tx_hash} has {} ids and {} values",
+                        token_ids.len(),
+                        amounts.len()
+                    ),
+                });
+            }
+            Ok(Transaction { sender, recipient, tx_hash, block_number, block_hash, log_index, transaction_index, operator: Some(operator), block_timestamp: None, receipt: None, transferred_token: TransferredToken::ERC1155 { address, token_ids, amounts } })
         } else {
             Err(IndexerError { reason: String::from("Bad event signature") })
         }
     }
 }
 
+pub struct NativeEventParser<'a> {
+    pub target_token: &'a Token,
+}
+
+impl<'a> EventParser for NativeEventParser<'a> {
+    fn parse(&self, _: &Log) -> Result<Transaction, IndexerError> {
+        Err(IndexerError {
+            reason: format!("Native token {} has no events, its transfers come from blocks", self.target_token.name),
+        })
+    }
+}
+
 pub fn get_event_parser<'a>(token: &'a Token) -> Box<dyn EventParser + 'a> {
     match &token.token_type {
         TokenType::ERC20 => Box::new(FungibleEventParser {
@@ -212,6 +288,105 @@ pub fn get_event_parser<'a>(token: &'a Token) -> Box<dyn EventParser + 'a> {
         TokenType::ERC721 => Box::new(NFTEventParser {
             target_token: token,
         }),
-        TokenType::ERC1155 => todo!(),
+        TokenType::ERC1155 => Box::new(ERC1155EventParser {
+            target_token: token,
+        }),
+        TokenType::Native => Box::new(NativeEventParser {
+            target_token: token,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use serde_json::json;
+    use web3::types::{Log, U256};
+
+    use super::{get_event_parser, TransferredToken};
+    use crate::models::{Token, TokenType};
+
+    fn erc1155_token() -> Token {
+        Token {
+            id: 1,
+            address: String::from("0x76be3b62873462d2142405439777e971754e8e77"),
+            name: String::from("parallel"),
+            strategy: String::from("token_scan"),
+            token_type: TokenType::ERC1155,
+            total_supply: Decimal::ZERO,
+            volume: Decimal::ZERO,
+            network_id: 1,
+        }
+    }
+
+    // TransferBatch(operator, from, to, ids = [10, 8003], values = [1, 25]) as returned by eth_getLogs
+    fn transfer_batch_log() -> Log {
+        serde_json::from_value(json!({
+            "address": "0x76be3b62873462d2142405439777e971754e8e77",
+            "topics": [
+                "0x4a39dc06d4c0dbc64b70af90fd698a233a518aa5d07e595d983b8c0526c8f7fb",
+                "0x0000000000000000000000001e0049783f008a0085193e00003d00cd54003c71",
+                "0x000000000000000000000000b4a8a1d2a3fd1e8e4a0cd1bd0c3d8d3fe2b49e0c",
+                "0x0000000000000000000000007f268357a8c2552623316e2562d90e642bb538e5"
+            ],
+            "data": concat!(
+                "0x",
+                "0000000000000000000000000000000000000000000000000000000000000040",
+                "00000000000000000000000000000000000000000000000000000000000000a0",
+                "0000000000000000000000000000000000000000000000000000000000000002",
+                "000000000000000000000000000000000000000000000000000000000000000a",
+                "0000000000000000000000000000000000000000000000000000000000001f43",
+                "0000000000000000000000000000000000000000000000000000000000000002",
+                "0000000000000000000000000000000000000000000000000000000000000001",
+                "0000000000000000000000000000000000000000000000000000000000000019"
+            ),
+            "blockNumber": "0x10d4f2a",
+            "blockHash": "0x7c5a35e9cb3e8ae0e221ab470abae9d446c3a5626ce6689fc777dcffcab52c70",
+            "transactionHash": "0xd7a2bba7bdbc8ec4e7ac5d4ba0b1b6ed0a0d0e7e5db1d07e3ac20ef7b7b2fd3a",
+            "transactionIndex": "0x4f",
+            "logIndex": "0xd2",
+            "removed": false
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_erc1155_transfer_batch() {
+        let token = erc1155_token();
+        let transaction = get_event_parser(&token).parse(&transfer_batch_log()).unwrap();
+        assert_eq!(transaction.operator.as_deref(), Some("0x1e0049783f008a0085193e00003d00cd54003c71"));
+        assert_eq!(transaction.sender, "0xb4a8a1d2a3fd1e8e4a0cd1bd0c3d8d3fe2b49e0c");
+        assert_eq!(transaction.recipient, "0x7f268357a8c2552623316e2562d90e642bb538e5");
+        assert_eq!(transaction.block_number, 0x10d4f2a);
+        assert_eq!(transaction.log_index, 0xd2);
+        match transaction.transferred_token {
+            TransferredToken::ERC1155 { token_ids, amounts, .. } => {
+                assert_eq!(token_ids, vec![U256::from(10), U256::from(8003)]);
+                assert_eq!(amounts, vec![U256::from(1), U256::from(25)]);
+            }
+            _ => panic!("expected ERC1155 transfer"),
+        }
+    }
+
+    #[test]
+    fn rejects_erc1155_transfer_batch_with_truncated_array() {
+        let token = erc1155_token();
+        let mut log = transfer_batch_log();
+        log.data.0.truncate(7 * 32);
+        assert!(get_event_parser(&token).parse(&log).is_err());
+    }
+
+    #[test]
+    fn rejects_erc1155_transfer_batch_with_unequal_arrays() {
+        let token = erc1155_token();
+        let mut log = transfer_batch_log();
+        // values array claims three items, the third one appended after the second
+        log.data.0[5 * 32 + 31] = 3;
+        log.data.0.extend_from_slice(&[0; 32]);
+        let parsed = get_event_parser(&token).parse(&log);
+        match parsed {
+            Ok(_) => panic!("batch with 2 ids and 3 values should be rejected"),
+            Err(e) => assert!(e.reason.contains("has 2 ids and 3 values"), "{}", e.reason),
+        }
     }
 }
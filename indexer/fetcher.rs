@@ -0,0 +1,433 @@
+use std::{collections::HashMap, str::FromStr};
+
+use log::{info, warn};
+use tokio::sync::{mpsc::Sender, watch};
+use web3::{
+    types::{Address, BlockId, Filter, FilterBuilder, Log, H256, U64},
+    Web3,
+};
+
+use crate::models::{HeadBlockTag, Network, Token, TokenType, TraceApi};
+
+use super::{
+    adaptive_step::AdaptiveStep,
+    byte_parsers::bytes20_to_address,
+    commons::{CycleError, IndexerError},
+    enrichment::{Enricher, Enrichment},
+    native::{fetch_native_transfers, NativeTransfer},
+    reorg::get_block_hash,
+    rpc_errors::{is_filter_not_found, is_limit_exceeded, is_method_not_supported},
+    traces::fetch_internal_transfers,
+    transports::RpcTransport,
+};
+
+const MAX_FILTER_ATTEMPTS: u32 = 3;
+// providers cap the number of addresses in a single eth_getLogs filter
+const MAX_ADDRESSES_PER_QUERY: usize = 100;
+// ranges fetched per cycle, so status changes are picked up during long catch-ups
+const MAX_RANGES_PER_CYCLE: u32 = 32;
+
+/// Logs and native transfers of one block range ready to be parsed and written.
+pub struct FetchedRange {
+    pub range: (u64, u64),
+    pub boundary_hash: String,
+    pub logs: Vec<Log>,
+    pub native_transfers: Vec<NativeTransfer>,
+    pub enrichment: Enrichment,
+}
+
+/// Fetches consecutive ranges from `from_block` towards `head` and sends them to the writer.
+/// Stops on the first error, at the head, when the writer hangs up or when stop is requested.
+#[allow(clippy::too_many_arguments)]
+pub async fn fetch_ranges(
+    web3: &Web3<RpcTransport>,
+    tokens: &[Token],
+    three_payload_topics: &[Option<Vec<H256>>; 3],
+    from_block: u64,
+    head: u64,
+    max_step: u64,
+    step: &mut AdaptiveStep,
+    enricher: &mut Enricher,
+    trace_api: Option<TraceApi>,
+    stop: &watch::Receiver<bool>,
+    sender: Sender<Result<FetchedRange, CycleError>>,
+) {
+    let mut from_block = from_block;
+    for _ in 0..MAX_RANGES_PER_CYCLE {
+        // ranges already sent are still written, nothing new is started
+        if *stop.borrow() {
+            return;
+        }
+        match fetch_range(web3, tokens, three_payload_topics, from_block, head, max_step, step, enricher, trace_api).await {
+            Ok(fetched) => {
+                let range = fetched.range;
+                if sender.send(Ok(fetched)).await.is_err() || range.1 >= head {
+                    return;
+                }
+                from_block = range.1;
+            }
+            Err(e) => {
+                let _ = sender.send(Err(e)).await;
+                return;
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn fetch_range(
+    web3: &Web3<RpcTransport>,
+    tokens: &[Token],
+    three_payload_topics: &[Option<Vec<H256>>; 3],
+    from_block: u64,
+    head: u64,
+    max_step: u64,
+    step: &mut AdaptiveStep,
+    enricher: &mut Enricher,
+    trace_api: Option<TraceApi>,
+) -> Result<FetchedRange, CycleError> {
+    loop {
+        let range = get_blocks_range(head, from_block, step.current(max_step));
+        info!("Fetching events from {} to {} blocks", range.0, range.1);
+        // boundary hash is taken before logs so a reorg in between is caught next cycle
+        let boundary_hash = get_block_hash(web3, range.1).await?;
+        match get_logs_for_tokens(web3, tokens, range, three_payload_topics).await {
+            Ok(logs) => {
+                step.record_success(max_step);
+                info!(
+                    "Fetched {} events for {} tokens with step {}",
+                    logs.len(),
+                    tokens.len(),
+                    step.current(max_step)
+                );
+                let mut native_transfers = vec![];
+                if tokens.iter().any(|token| token.token_type.is_native()) {
+                    native_transfers = fetch_native_transfers(web3, range, three_payload_topics)
+                        .await
+                        .map_err(CycleError::Transient)?;
+                    if let Some(trace_api) = trace_api {
+                        let internal_transfers = fetch_internal_transfers(web3, range, trace_api, three_payload_topics)
+                            .await
+                            .map_err(CycleError::Transient)?;
+                        info!("Fetched {} internal transfers with {trace_api} traces", internal_transfers.len());
+                        native_transfers.extend(internal_transfers);
+                    }
+                }
+                let enrichment = enricher.enrich(web3, &logs, &native_transfers).await?;
+                return Ok(FetchedRange {
+                    range,
+                    boundary_hash,
+                    logs,
+                    native_transfers,
+                    enrichment,
+                });
+            }
+            Err(LogsError::LimitExceeded(reason)) => {
+                warn!("Provider refused range {} to {}: {reason}", range.0, range.1);
+                if !step.shrink(max_step) {
+                    return Err(CycleError::Transient(IndexerError {
+                        reason: format!("Provider refused even a single block range: {reason}"),
+                    }));
+                }
+            }
+            Err(LogsError::BadToken(e)) => return Err(CycleError::Fatal(e)),
+            Err(LogsError::Failed(e)) => return Err(CycleError::Transient(e)),
+        }
+    }
+}
+
+pub async fn get_block_number(web3: &Web3<RpcTransport>, network: &Network) -> Result<u64, IndexerError> {
+    let head = match network.head_block_tag {
+        HeadBlockTag::Latest => match web3.eth().block_number().await {
+            Ok(number) => number.as_u64(),
+            Err(e) => {
+                return Err(IndexerError {
+                    reason: e.to_string(),
+                })
+            }
+        },
+        _ => {
+            let block_id = BlockId::Number(network.head_block_tag.as_block_number());
+            match web3.eth().block(block_id).await {
+                Ok(Some(block)) => match block.number {
+                    Some(number) => number.as_u64(),
+                    None => {
+                        return Err(IndexerError {
+                            reason: format!("Block tagged {} has no number", network.head_block_tag),
+                        })
+                    }
+                },
+                Ok(None) => {
+                    return Err(IndexerError {
+                        reason: format!("Node does not support block tag {}", network.head_block_tag),
+                    })
+                }
+                Err(e) => {
+                    return Err(IndexerError {
+                        reason: format!("During fetching {} block occurred {e}", network.head_block_tag),
+                    })
+                }
+            }
+        }
+    };
+    let confirmed_head = head.saturating_sub(network.confirmations);
+    info!(
+        "Head block by tag {} is {head}, with {} confirmations indexing up to {confirmed_head}",
+        network.head_block_tag, network.confirmations
+    );
+    Ok(confirmed_head)
+}
+
+fn get_blocks_range(latest_block_blockchain: u64, latest_block_db: u64, step: u64) -> (u64, u64) {
+    // confirmed head may be behind last indexed block, never move indexer backwards
+    let upper_bound = std::cmp::min(latest_block_blockchain, latest_block_db + step);
+    (latest_block_db, std::cmp::max(latest_block_db, upper_bound))
+}
+
+fn build_filter(
+    addresses: Vec<Address>,
+    block_range: (u64, u64),
+    topics: &[Option<Vec<H256>>; 4],
+) -> Filter {
+    FilterBuilder::default()
+        .address(addresses)
+        .from_block(web3::types::BlockNumber::Number(U64::from(block_range.0)))
+        .to_block(web3::types::BlockNumber::Number(U64::from(block_range.1)))
+        .topics(
+            topics[0].clone(),
+            topics[1].clone(),
+            topics[2].clone(),
+            topics[3].clone(),
+        )
+        .build()
+}
+
+enum LogsError {
+    LimitExceeded(String),
+    BadToken(IndexerError),
+    Failed(IndexerError),
+}
+
+/// Fetches logs of every watched token with as few eth_getLogs calls as possible:
+/// all addresses and all event signatures go into one filter per addresses chunk.
+async fn get_logs_for_tokens(
+    web3: &Web3<RpcTransport>,
+    tokens: &[Token],
+    block_range: (u64, u64),
+    three_payload_topics: &[Option<Vec<H256>>; 3],
+) -> Result<Vec<Log>, LogsError> {
+    let mut logs = vec![];
+    for (addresses, topics) in plan_log_queries(tokens, three_payload_topics).map_err(LogsError::BadToken)? {
+        let filter = build_filter(addresses, block_range, &topics);
+        logs.extend(get_logs(web3, filter).await?);
+    }
+    Ok(logs)
+}
+
+// addresses and four topics of one eth_getLogs filter
+type LogQuery = (Vec<Address>, [Option<Vec<H256>>; 4]);
+
+/// Queries covering logs of the tokens.
+/// ERC1155 tokens are queried apart, as their events name the operator before sender and recipient.
+fn plan_log_queries(tokens: &[Token], three_payload_topics: &[Option<Vec<H256>>; 3]) -> Result<Vec<LogQuery>, IndexerError> {
+    let (erc1155_tokens, other_tokens): (Vec<&Token>, Vec<&Token>) = tokens
+        .iter()
+        .filter(|token| !token.token_type.is_native())
+        .partition(|token| matches!(token.token_type, TokenType::ERC1155));
+    let erc1155_payload_topics = [None, three_payload_topics[0].clone(), three_payload_topics[1].clone()];
+    let mut queries = vec![];
+    for (tokens, payload_topics) in [(other_tokens, three_payload_topics), (erc1155_tokens, &erc1155_payload_topics)] {
+        let mut addresses = vec![];
+        let mut events: Vec<H256> = vec![];
+        for token in tokens.iter() {
+            match Address::from_str(token.address.as_str()) {
+                Ok(address) => addresses.push(address),
+                Err(e) => {
+                    return Err(IndexerError {
+                        reason: format!("Token {} has bad address {}: {e}", token.name, token.address),
+                    })
+                }
+            }
+            for event in token.token_type.get_events_hashes() {
+                if !events.contains(&event) {
+                    events.push(event);
+                }
+            }
+        }
+        // a filter without addresses would match logs of every contract
+        if addresses.is_empty() {
+            continue;
+        }
+        let topics = [
+            Some(events),
+            payload_topics[0].clone(),
+            payload_topics[1].clone(),
+            payload_topics[2].clone(),
+        ];
+        for addresses_chunk in addresses.chunks(MAX_ADDRESSES_PER_QUERY) {
+            queries.push((addresses_chunk.to_vec(), topics.clone()));
+        }
+    }
+    Ok(queries)
+}
+
+/// Groups logs by emitting token, skipping events which the token type does not handle.
+pub fn route_logs_to_tokens<'a>(tokens: &'a [Token], logs: &[Log]) -> Vec<(&'a Token, Vec<Log>)> {
+    let mut tokens_by_address: HashMap<String, usize> = HashMap::new();
+    // native token emits no logs, its address may well be taken by a contract
+    for (i, token) in tokens.iter().enumerate().filter(|(_, token)| !token.token_type.is_native()) {
+        tokens_by_address.insert(token.address.to_lowercase(), i);
+    }
+    let mut routed: Vec<Vec<Log>> = tokens.iter().map(|_| vec![]).collect();
+    for log in logs.iter() {
+        let address = bytes20_to_address(&log.address);
+        let Some(&i) = tokens_by_address.get(&address) else {
+            warn!("Skipping event from not watched address {address}");
+            continue;
+        };
+        let handled_events = tokens[i].token_type.get_events_hashes();
+        if log.topics.first().is_some_and(|topic| handled_events.contains(topic)) {
+            routed[i].push(log.clone());
+        }
+    }
+    tokens.iter().zip(routed).collect()
+}
+
+async fn get_logs(web3: &Web3<RpcTransport>, filter: Filter) -> Result<Vec<Log>, LogsError> {
+    match web3.eth().logs(filter.clone()).await {
+        Ok(logs) => Ok(logs),
+        Err(e) if is_limit_exceeded(&e) => Err(LogsError::LimitExceeded(e.to_string())),
+        Err(e) if is_method_not_supported(&e) => {
+            warn!("Node does not serve eth_getLogs ({e}), falling back to stateful log filter");
+            get_logs_with_filter(web3, filter).await
+        }
+        Err(e) => Err(LogsError::Failed(IndexerError {
+            reason: format!("Error occurred on logs fetching {e}"),
+        })),
+    }
+}
+
+async fn get_logs_with_filter(web3: &Web3<RpcTransport>, filter: Filter) -> Result<Vec<Log>, LogsError> {
+    let mut attempt = 1;
+    loop {
+        let base_filter = match web3.eth_filter().create_logs_filter(filter.clone()).await {
+            Ok(base_filter) => base_filter,
+            Err(e) if is_limit_exceeded(&e) => return Err(LogsError::LimitExceeded(e.to_string())),
+            Err(e) => {
+                return Err(LogsError::Failed(IndexerError {
+                    reason: format!("During establishing new filter {e} occurred"),
+                }))
+            }
+        };
+        match base_filter.logs().await {
+            Ok(logs) => return Ok(logs),
+            // filter was created on another node behind the proxy, so establish it again
+            Err(e) if is_filter_not_found(&e) && attempt < MAX_FILTER_ATTEMPTS => {
+                warn!("Log filter lost on attempt {attempt}: {e}");
+                attempt += 1;
+            }
+            Err(e) if is_limit_exceeded(&e) => return Err(LogsError::LimitExceeded(e.to_string())),
+            Err(e) => {
+                return Err(LogsError::Failed(IndexerError {
+                    reason: format!("Error occurred on logs fetching {e}"),
+                }))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
+    use serde_json::json;
+    use web3::types::{Log, H256};
+
+    use super::{plan_log_queries, route_logs_to_tokens, MAX_ADDRESSES_PER_QUERY};
+    use crate::models::{Token, TokenType};
+
+    fn token(id: i64, address: &str, token_type: TokenType) -> Token {
+        Token {
+            id,
+            address: String::from(address),
+            name: format!("token-{id}"),
+            strategy: String::from("token_scan"),
+            token_type,
+            total_supply: Decimal::ZERO,
+            volume: Decimal::ZERO,
+            network_id: 1,
+        }
+    }
+
+    #[test]
+    fn moves_payload_topics_past_erc1155_operator() {
+        let tokens = [
+            token(1, "0xdac17f958d2ee523a2206206994597c13d831ec7", TokenType::ERC20),
+            token(2, "0x76be3b62873462d2142405439777e971754e8e77", TokenType::ERC1155),
+            token(3, "0x0000000000000000000000000000000000000000", TokenType::Native),
+        ];
+        let recipient = H256::from_low_u64_be(0xbeef);
+        let queries = plan_log_queries(&tokens, &[None, Some(vec![recipient]), None]).unwrap();
+
+        assert_eq!(queries.len(), 2);
+        let (addresses, topics) = &queries[0];
+        assert_eq!(addresses, &vec![tokens[0].address.parse().unwrap()]);
+        assert_eq!(topics[0], Some(TokenType::ERC20.get_events_hashes()));
+        assert_eq!(topics[1..], [None, Some(vec![recipient]), None]);
+        let (addresses, topics) = &queries[1];
+        assert_eq!(addresses, &vec![tokens[1].address.parse().unwrap()]);
+        assert_eq!(topics[0], Some(TokenType::ERC1155.get_events_hashes()));
+        assert_eq!(topics[1..], [None, None, Some(vec![recipient])]);
+    }
+
+    fn log(address: &str, topic0: H256, log_index: u64) -> Log {
+        serde_json::from_value(json!({
+            "address": address,
+            "topics": [topic0],
+            "data": "0x",
+            "logIndex": format!("{log_index:#x}")
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn routes_logs_to_tokens_by_address_and_event() {
+        let tokens = [
+            token(1, "0xdAC17F958D2ee523a2206206994597C13D831ec7", TokenType::ERC20),
+            token(2, "0xbc4ca0eda7647a8ab7c2061c2e118a18a936f13d", TokenType::ERC721),
+            token(3, "0x76be3b62873462d2142405439777e971754e8e77", TokenType::ERC1155),
+            // native token sharing an address must not take logs of the contract
+            token(4, "0xbc4ca0eda7647a8ab7c2061c2e118a18a936f13d", TokenType::Native),
+        ];
+        // ERC20 and ERC721 share the Transfer signature
+        let transfer = TokenType::ERC20.get_events_hashes()[0];
+        let erc1155_events = TokenType::ERC1155.get_events_hashes();
+        let logs = [
+            log("0xdac17f958d2ee523a2206206994597c13d831ec7", transfer, 0),
+            log("0xbc4ca0eda7647a8ab7c2061c2e118a18a936f13d", transfer, 1),
+            log("0x76be3b62873462d2142405439777e971754e8e77", erc1155_events[0], 2),
+            log("0x76be3b62873462d2142405439777e971754e8e77", erc1155_events[1], 3),
+            // Transfer is not an event of ERC1155 tokens
+            log("0x76be3b62873462d2142405439777e971754e8e77", transfer, 4),
+            log("0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48", transfer, 5),
+            log("0xdac17f958d2ee523a2206206994597c13d831ec7", transfer, 6),
+        ];
+
+        let routed = route_logs_to_tokens(&tokens, &logs);
+
+        let log_indexes: Vec<(i64, Vec<u64>)> = routed
+            .iter()
+            .map(|(token, logs)| (token.id, logs.iter().map(|log| log.log_index.unwrap().as_u64()).collect()))
+            .collect();
+        assert_eq!(log_indexes, vec![(1, vec![0, 6]), (2, vec![1]), (3, vec![2, 3]), (4, vec![])]);
+    }
+
+    #[test]
+    fn chunks_addresses_of_one_query() {
+        let tokens: Vec<Token> = (1..=MAX_ADDRESSES_PER_QUERY as u64 + 1)
+            .map(|id| token(id as i64, &format!("{:?}", web3::types::H160::from_low_u64_be(id)), TokenType::ERC20))
+            .collect();
+        let queries = plan_log_queries(&tokens, &[None, None, None]).unwrap();
+        let chunk_sizes: Vec<usize> = queries.iter().map(|(addresses, _)| addresses.len()).collect();
+        assert_eq!(chunk_sizes, vec![MAX_ADDRESSES_PER_QUERY, 1]);
+    }
+}
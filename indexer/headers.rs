@@ -0,0 +1,229 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use log::info;
+use serde_json::Value;
+use web3::{
+    types::{Block, H256, U64},
+    BatchTransport, Transport, Web3,
+};
+
+use super::{byte_parsers::bytes32_to_string, commons::IndexerError, transports::RpcTransport};
+
+const HEADER_CACHE_CAPACITY: usize = 4096;
+const MAX_HEADERS_PER_BATCH: usize = 100;
+
+struct CachedHeader {
+    block_hash: String,
+    timestamp: u64,
+    used_at: u64,
+}
+
+/// Bounded LRU cache of block timestamps, so each header is fetched once per indexer.
+pub struct HeaderCache {
+    headers: HashMap<u64, CachedHeader>,
+    // use tick to block number, the first entry is the least recently used
+    recency: BTreeMap<u64, u64>,
+    tick: u64,
+    capacity: usize,
+    need_poa: bool,
+}
+
+impl HeaderCache {
+    pub fn new() -> Self {
+        Self::with_capacity(HEADER_CACHE_CAPACITY)
+    }
+
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            headers: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            capacity,
+            need_poa: false,
+        }
+    }
+
+    /// PoA chains put signatures into extraData, their headers are read field by field.
+    pub fn set_need_poa(&mut self, need_poa: bool) {
+        self.need_poa = need_poa;
+    }
+
+    /// Timestamps of the given blocks. Block hash is compared too, so headers
+    /// of blocks replaced by a reorg are fetched again.
+    pub async fn get_timestamps(
+        &mut self,
+        web3: &Web3<RpcTransport>,
+        blocks: &[(u64, String)],
+    ) -> Result<HashMap<u64, u64>, IndexerError> {
+        let mut timestamps = HashMap::new();
+        let mut missing = BTreeSet::new();
+        for (block_number, block_hash) in blocks.iter() {
+            match self.get(*block_number, block_hash) {
+                Some(timestamp) => {
+                    timestamps.insert(*block_number, timestamp);
+                }
+                None => {
+                    missing.insert(*block_number);
+                }
+            }
+        }
+        let missing: Vec<u64> = missing.into_iter().collect();
+        if !missing.is_empty() {
+            info!("Fetching {} block headers, {} found in cache", missing.len(), timestamps.len());
+        }
+        for batch in missing.chunks(MAX_HEADERS_PER_BATCH) {
+            for (block_number, block_hash, timestamp) in fetch_headers(web3, batch, self.need_poa).await? {
+                self.insert(block_number, block_hash, timestamp);
+                timestamps.insert(block_number, timestamp);
+            }
+        }
+        for (block_number, block_hash) in blocks.iter() {
+            if self.get(*block_number, block_hash).is_none() {
+                return Err(IndexerError {
+                    reason: format!("Block {block_number} is no more {block_hash} on node, chain was reorganized"),
+                });
+            }
+        }
+        Ok(timestamps)
+    }
+
+    fn get(&mut self, block_number: u64, block_hash: &str) -> Option<u64> {
+        self.tick += 1;
+        let header = self.headers.get_mut(&block_number)?;
+        if header.block_hash != block_hash {
+            return None;
+        }
+        self.recency.remove(&header.used_at);
+        header.used_at = self.tick;
+        self.recency.insert(self.tick, block_number);
+        Some(header.timestamp)
+    }
+
+    fn insert(&mut self, block_number: u64, block_hash: String, timestamp: u64) {
+        self.tick += 1;
+        if let Some(previous) = self.headers.remove(&block_number) {
+            self.recency.remove(&previous.used_at);
+        }
+        while self.headers.len() >= self.capacity {
+            let Some((_, evicted)) = self.recency.pop_first() else {
+                break;
+            };
+            self.headers.remove(&evicted);
+        }
+        self.headers.insert(
+            block_number,
+            CachedHeader {
+                block_hash,
+                timestamp,
+                used_at: self.tick,
+            },
+        );
+        self.recency.insert(self.tick, block_number);
+    }
+}
+
+/// Fetches headers with one batched `eth_getBlockByNumber` call.
+async fn fetch_headers(
+    web3: &Web3<RpcTransport>,
+    block_numbers: &[u64],
+    need_poa: bool,
+) -> Result<Vec<(u64, String, u64)>, IndexerError> {
+    let transport = web3.transport();
+    let requests: Vec<_> = block_numbers
+        .iter()
+        .map(|block_number| {
+            transport.prepare(
+                "eth_getBlockByNumber",
+                vec![Value::String(format!("{:#x}", block_number)), Value::Bool(false)],
+            )
+        })
+        .collect();
+    let responses = match transport.send_batch(requests).await {
+        Ok(responses) => responses,
+        Err(e) => {
+            return Err(IndexerError {
+                reason: format!("During fetching block headers occurred {e}"),
+            })
+        }
+    };
+    let mut headers = vec![];
+    for (block_number, response) in block_numbers.iter().zip(responses) {
+        let header = match response {
+            Ok(Value::Null) => {
+                return Err(IndexerError {
+                    reason: format!("Block {block_number} not found on node"),
+                })
+            }
+            Ok(value) if need_poa => parse_poa_header(&value),
+            Ok(value) => parse_header(value),
+            Err(e) => Err(format!("{e}")),
+        };
+        match header {
+            Ok((block_hash, timestamp)) => headers.push((*block_number, block_hash, timestamp)),
+            Err(reason) => {
+                return Err(IndexerError {
+                    reason: format!("During reading header of block {block_number} occurred {reason}"),
+                })
+            }
+        }
+    }
+    Ok(headers)
+}
+
+fn parse_header(value: Value) -> Result<(String, u64), String> {
+    let block: Block<H256> = match serde_json::from_value(value) {
+        Ok(block) => block,
+        Err(e) => return Err(format!("{e}, set need_poa on the network if it is a PoA chain")),
+    };
+    match block.hash {
+        Some(hash) => Ok((bytes32_to_string(&hash), block.timestamp.as_u64())),
+        None => Err(String::from("header has no hash")),
+    }
+}
+
+/// Reads only hash and timestamp, skipping oversized extraData and other non standard fields.
+fn parse_poa_header(value: &Value) -> Result<(String, u64), String> {
+    let hash = match value.get("hash").map(|hash| serde_json::from_value::<H256>(hash.clone())) {
+        Some(Ok(hash)) => hash,
+        _ => return Err(String::from("header has no hash")),
+    };
+    let timestamp = match value.get("timestamp").map(|timestamp| serde_json::from_value::<U64>(timestamp.clone())) {
+        Some(Ok(timestamp)) => timestamp,
+        _ => return Err(String::from("header has no timestamp")),
+    };
+    Ok((bytes32_to_string(&hash), timestamp.as_u64()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HeaderCache;
+
+    #[test]
+    fn evicts_least_recently_used_header() {
+        let mut cache = HeaderCache::with_capacity(2);
+        cache.insert(1, String::from("0x01"), 100);
+        cache.insert(2, String::from("0x02"), 200);
+        // reading block 1 makes block 2 the least recently used one
+        assert_eq!(cache.get(1, "0x01"), Some(100));
+        cache.insert(3, String::from("0x03"), 300);
+
+        assert_eq!(cache.get(2, "0x02"), None);
+        assert_eq!(cache.get(1, "0x01"), Some(100));
+        assert_eq!(cache.get(3, "0x03"), Some(300));
+        assert_eq!(cache.headers.len(), 2);
+        assert_eq!(cache.recency.len(), 2);
+    }
+
+    #[test]
+    fn misses_header_of_reorged_block() {
+        let mut cache = HeaderCache::with_capacity(2);
+        cache.insert(1, String::from("0x01"), 100);
+        assert_eq!(cache.get(1, "0x0a"), None);
+
+        // header fetched again replaces the orphaned one without growing the cache
+        cache.insert(1, String::from("0x0a"), 101);
+        assert_eq!(cache.get(1, "0x0a"), Some(101));
+        assert_eq!(cache.headers.len(), 1);
+        assert_eq!(cache.recency.len(), 1);
+    }
+}
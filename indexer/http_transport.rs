@@ -0,0 +1,198 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use futures::future::BoxFuture;
+use jsonrpc_core::{Call, Id, Output, Request, Value};
+use reqwest::{header::RETRY_AFTER, StatusCode};
+use web3::{
+    error::{Error, TransportError},
+    helpers, BatchTransport, RequestId, Transport,
+};
+
+use super::rate_limiter::RateLimiter;
+
+// pause when a 429 response has no usable Retry-After header
+const DEFAULT_RETRY_AFTER_SECONDS: u64 = 1;
+
+/// Bounds of one HTTP call, so a hung endpoint fails instead of blocking the indexer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HttpTimeouts {
+    pub request: Duration,
+    pub connect: Duration,
+}
+
+/// HTTP JSON-RPC transport which, unlike web3 Http, honors `Retry-After` of 429 responses.
+#[derive(Debug, Clone)]
+pub struct HttpTransport {
+    client: reqwest::Client,
+    url: reqwest::Url,
+    limiter: Arc<RateLimiter>,
+}
+
+impl HttpTransport {
+    pub fn new(url: &str, limiter: Arc<RateLimiter>, timeouts: HttpTimeouts) -> Result<Self, Error> {
+        let url = match reqwest::Url::parse(url) {
+            Ok(url) => url,
+            Err(e) => return Err(Error::Transport(TransportError::Message(e.to_string()))),
+        };
+        let client = match reqwest::Client::builder()
+            .timeout(timeouts.request)
+            .connect_timeout(timeouts.connect)
+            .build()
+        {
+            Ok(client) => client,
+            Err(e) => return Err(Error::Transport(TransportError::Message(e.to_string()))),
+        };
+        Ok(Self { client, url, limiter })
+    }
+}
+
+/// `Retry-After` is either delay in seconds or HTTP-date to retry at.
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let retry_at = httpdate::parse_http_date(value).ok()?;
+    // date in the past allows retrying right away
+    Some(retry_at.duration_since(now).unwrap_or(Duration::ZERO))
+}
+
+impl HttpTransport {
+    async fn post(&self, request: &Request) -> Result<Vec<u8>, Error> {
+        let response = match self.client.post(self.url.clone()).json(request).send().await {
+            Ok(response) => response,
+            Err(e) => {
+                return Err(Error::Transport(TransportError::Message(format!(
+                    "failed to send request: {e}"
+                ))))
+            }
+        };
+        let status = response.status();
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| parse_retry_after(value, SystemTime::now()))
+                .unwrap_or(Duration::from_secs(DEFAULT_RETRY_AFTER_SECONDS));
+            self.limiter.pause_for(retry_after);
+        }
+        let body = match response.bytes().await {
+            Ok(body) => body,
+            Err(e) => {
+                return Err(Error::Transport(TransportError::Message(format!(
+                    "failed to read response bytes: {e}"
+                ))))
+            }
+        };
+        if !status.is_success() {
+            return Err(Error::Transport(TransportError::Code(status.as_u16())));
+        }
+        Ok(body.to_vec())
+    }
+}
+
+fn deserialize(body: &[u8]) -> Result<Value, Error> {
+    match helpers::arbitrary_precision_deserialize_workaround::<Value>(body) {
+        Ok(value) => Ok(value),
+        Err(e) => Err(Error::Transport(TransportError::Message(format!(
+            "failed to deserialize response: {e}: {}",
+            String::from_utf8_lossy(body)
+        )))),
+    }
+}
+
+impl Transport for HttpTransport {
+    type Out = BoxFuture<'static, web3::error::Result<Value>>;
+
+    fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+        // ids are assigned by the failover transport, see RpcTransport
+        (0, helpers::build_request(0, method, params))
+    }
+
+    fn send(&self, _id: RequestId, call: Call) -> Self::Out {
+        let transport = self.clone();
+        Box::pin(async move {
+            let body = transport.post(&Request::Single(call)).await?;
+            let output: Output = serde_json::from_value(deserialize(&body)?)?;
+            helpers::to_result_from_output(output)
+        })
+    }
+}
+
+impl BatchTransport for HttpTransport {
+    type Batch = BoxFuture<'static, web3::error::Result<Vec<web3::error::Result<Value>>>>;
+
+    fn send_batch<T>(&self, requests: T) -> Self::Batch
+    where
+        T: IntoIterator<Item = (RequestId, Call)>,
+    {
+        let transport = self.clone();
+        let (ids, calls): (Vec<RequestId>, Vec<Call>) = requests.into_iter().unzip();
+        Box::pin(async move {
+            let body = transport.post(&Request::Batch(calls)).await?;
+            let value = deserialize(&body)?;
+            // a single error object answers the whole batch, e.g. when batches are not supported
+            if value.is_object() {
+                return match serde_json::from_value::<Output>(value)? {
+                    Output::Failure(failure) => Err(Error::Rpc(failure.error)),
+                    Output::Success(_) => Err(Error::InvalidResponse(String::from(
+                        "Single response for batched request",
+                    ))),
+                };
+            }
+            let outputs: Vec<Output> = serde_json::from_value(value)?;
+            // responses of a batch may come in any order
+            let mut results: HashMap<RequestId, web3::error::Result<Value>> = HashMap::new();
+            for output in outputs {
+                let id = match output.id() {
+                    Id::Num(id) => *id as RequestId,
+                    _ => return Err(Error::InvalidResponse(String::from("Response id is not a number"))),
+                };
+                results.insert(id, helpers::to_result_from_output(output));
+            }
+            ids.iter()
+                .map(|id| match results.remove(id) {
+                    Some(result) => Ok(result),
+                    None => Err(Error::InvalidResponse(format!("Batch response is missing id {id}"))),
+                })
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use super::parse_retry_after;
+
+    #[test]
+    fn parses_retry_after_seconds() {
+        assert_eq!(parse_retry_after(" 120 ", SystemTime::now()), Some(Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn parses_retry_after_http_date() {
+        // Wed, 21 Oct 2015 07:28:00 GMT
+        let retry_at = UNIX_EPOCH + Duration::from_secs(1_445_412_480);
+        let now = retry_at - Duration::from_secs(30);
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", retry_at + Duration::from_secs(5)),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_retry_after() {
+        assert_eq!(parse_retry_after("soon", SystemTime::now()), None);
+    }
+}
@@ -0,0 +1,192 @@
+use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    fmt::Write,
+    net::SocketAddr,
+    sync::Mutex,
+    time::Duration,
+};
+
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use log::{info, warn};
+
+enum Kind {
+    Gauge,
+    Counter,
+    Histogram,
+}
+
+// every exported metric with its type and help, in the order they are rendered
+const METRICS: &[(&str, Kind, &str)] = &[
+    ("evm_indexer_chain_head", Kind::Gauge, "Confirmed head block of the network as seen by the indexer"),
+    ("evm_indexer_last_block", Kind::Gauge, "Last block indexed"),
+    ("evm_indexer_lag_blocks", Kind::Gauge, "Blocks between confirmed head and last indexed block"),
+    ("evm_indexer_step_blocks", Kind::Gauge, "Current adaptive step of eth_getLogs ranges"),
+    ("evm_indexer_ranges_processed_total", Kind::Counter, "Block ranges committed"),
+    ("evm_indexer_logs_fetched_total", Kind::Counter, "Events and native transfers fetched per token"),
+    ("evm_indexer_transfers_saved_total", Kind::Counter, "Token transfer rows saved per token"),
+    ("evm_indexer_logs_skipped_total", Kind::Counter, "Malformed events skipped per token"),
+    ("evm_indexer_rpc_duration_seconds", Kind::Histogram, "Latency of RPC calls by method"),
+    ("evm_indexer_rpc_errors_total", Kind::Counter, "Failed RPC calls by method"),
+    ("evm_indexer_db_commit_duration_seconds", Kind::Histogram, "Latency of committing a range to the database"),
+];
+
+// upper bounds in seconds, shared by all histograms
+const BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+enum Series {
+    Value(f64),
+    Histogram { counts: Vec<u64>, sum: f64, count: u64 },
+}
+
+// metric name to rendered labels to series
+static REGISTRY: Mutex<BTreeMap<&'static str, BTreeMap<String, Series>>> = Mutex::new(BTreeMap::new());
+
+fn render_labels(labels: &[(&str, &str)]) -> String {
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(key, value)| {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+            format!("{key}=\"{value}\"")
+        })
+        .collect();
+    labels.join(",")
+}
+
+pub fn set_gauge(name: &'static str, labels: &[(&str, &str)], value: f64) {
+    let mut registry = REGISTRY.lock().unwrap();
+    registry.entry(name).or_default().insert(render_labels(labels), Series::Value(value));
+}
+
+pub fn inc_counter(name: &'static str, labels: &[(&str, &str)], by: u64) {
+    let mut registry = REGISTRY.lock().unwrap();
+    let series = registry.entry(name).or_default().entry(render_labels(labels)).or_insert(Series::Value(0.0));
+    if let Series::Value(value) = series {
+        *value += by as f64;
+    }
+}
+
+pub fn observe(name: &'static str, labels: &[(&str, &str)], duration: Duration) {
+    let seconds = duration.as_secs_f64();
+    let mut registry = REGISTRY.lock().unwrap();
+    let series = registry.entry(name).or_default().entry(render_labels(labels)).or_insert(Series::Histogram {
+        counts: vec![0; BUCKETS.len()],
+        sum: 0.0,
+        count: 0,
+    });
+    if let Series::Histogram { counts, sum, count } = series {
+        for (bucket, upper_bound) in counts.iter_mut().zip(BUCKETS.iter()) {
+            if seconds <= *upper_bound {
+                *bucket += 1;
+            }
+        }
+        *sum += seconds;
+        *count += 1;
+    }
+}
+
+/// Sets last block and lag, against the new head if given or the last one seen otherwise.
+pub fn set_progress(indexer: &str, network: &str, head: Option<u64>, last_block: u64) {
+    let labels = [("indexer", indexer), ("network", network)];
+    set_gauge("evm_indexer_last_block", &labels, last_block as f64);
+    let head = match head {
+        Some(head) => {
+            set_gauge("evm_indexer_chain_head", &labels, head as f64);
+            head
+        }
+        None => {
+            let registry = REGISTRY.lock().unwrap();
+            match registry.get("evm_indexer_chain_head").and_then(|series| series.get(&render_labels(&labels))) {
+                Some(Series::Value(head)) => *head as u64,
+                _ => return,
+            }
+        }
+    };
+    set_gauge("evm_indexer_lag_blocks", &labels, head.saturating_sub(last_block) as f64);
+}
+
+/// Prometheus text exposition of everything recorded so far.
+pub fn render() -> String {
+    let registry = REGISTRY.lock().unwrap();
+    let mut output = String::new();
+    for (name, kind, help) in METRICS.iter() {
+        let Some(series) = registry.get(name) else {
+            continue;
+        };
+        let kind_name = match kind {
+            Kind::Gauge => "gauge",
+            Kind::Counter => "counter",
+            Kind::Histogram => "histogram",
+        };
+        let _ = writeln!(output, "# HELP {name} {help}");
+        let _ = writeln!(output, "# TYPE {name} {kind_name}");
+        for (labels, series) in series.iter() {
+            match series {
+                Series::Value(value) => {
+                    let _ = writeln!(output, "{name}{{{labels}}} {value}");
+                }
+                Series::Histogram { counts, sum, count } => {
+                    let separator = if labels.is_empty() { "" } else { "," };
+                    for (bucket, upper_bound) in counts.iter().zip(BUCKETS.iter()) {
+                        let _ = writeln!(output, "{name}_bucket{{{labels}{separator}le=\"{upper_bound}\"}} {bucket}");
+                    }
+                    let _ = writeln!(output, "{name}_bucket{{{labels}{separator}le=\"+Inf\"}} {count}");
+                    let _ = writeln!(output, "{name}_sum{{{labels}}} {sum}");
+                    let _ = writeln!(output, "{name}_count{{{labels}}} {count}");
+                }
+            }
+        }
+    }
+    output
+}
+
+async fn handle(request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let response = if request.uri().path() == "/metrics" {
+        Response::builder()
+            .header("Content-Type", "text/plain; version=0.0.4")
+            .body(Body::from(render()))
+    } else {
+        Response::builder().status(StatusCode::NOT_FOUND).body(Body::empty())
+    };
+    Ok(response.unwrap_or_default())
+}
+
+/// Serves `/metrics` on the given METRICS_ADDRESS in the background.
+pub fn serve_metrics(address: &str) {
+    let address = match address.parse::<SocketAddr>() {
+        Ok(address) => address,
+        Err(e) => {
+            warn!("METRICS_ADDRESS {address} is not a socket address, metrics are not served: {e}");
+            return;
+        }
+    };
+    let server = match Server::try_bind(&address) {
+        Ok(builder) => builder.serve(make_service_fn(|_| async { Ok::<_, Infallible>(service_fn(handle)) })),
+        Err(e) => {
+            warn!("Metrics could not listen on {address}: {e}");
+            return;
+        }
+    };
+    info!("Serving metrics on http://{address}/metrics");
+    tokio::spawn(async move {
+        if let Err(e) = server.await {
+            warn!("Metrics server stopped with {e}");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_labels;
+
+    #[test]
+    fn escapes_label_values() {
+        assert_eq!(
+            render_labels(&[("indexer", "usdt"), ("token", "a\\b \"c\"\nd")]),
+            "indexer=\"usdt\",token=\"a\\\\b \\\"c\\\"\\nd\""
+        );
+    }
+}
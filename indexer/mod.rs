@@ -1,7 +1,33 @@
+pub mod admin;
+pub mod backfill;
 pub mod cycle;
+pub mod dry_run;
+pub mod enrichment;
 pub mod commons;
 pub mod strategies;
+pub mod subscription;
 pub mod transactions;
 pub mod event_parsers;
+pub mod fetcher;
+pub mod native;
+pub mod headers;
+pub mod metrics;
+pub mod http_transport;
 pub mod byte_parsers;
+pub mod adaptive_step;
+pub mod backoff;
+pub mod rate_limiter;
+pub mod receipts;
+pub mod reorg;
+pub mod rpc_errors;
+pub mod shutdown;
+pub mod supervisor;
+pub mod traces;
+pub mod transports;
+pub use admin::{list, reindex, status};
+pub use backfill::{backfill, BackfillOptions};
 pub use cycle::start;
+pub use dry_run::dry_run;
+pub use metrics::serve_metrics;
+pub use shutdown::{listen_for_signals, ExitStatus};
+pub use supervisor::supervise;
@@ -0,0 +1,351 @@
+use serde_json::Value;
+use web3::{
+    types::{H160, H256, U256, U64},
+    BatchTransport, Transport, Web3,
+};
+
+use crate::models::Token;
+
+use super::{
+    byte_parsers::{bytes20_to_address, bytes32_to_string},
+    commons::IndexerError,
+    transactions::{Transaction, TransferredToken},
+    transports::RpcTransport,
+};
+
+// blocks come with full transactions, so batches are kept small
+pub const MAX_BLOCKS_PER_BATCH: usize = 10;
+const MAX_RECEIPTS_PER_BATCH: usize = 100;
+
+/// Value moved by a top level transaction, which unlike tokens emits no event.
+pub struct NativeTransfer {
+    pub sender: String,
+    pub recipient: String,
+    pub tx_hash: String,
+    pub block_number: u64,
+    pub block_hash: String,
+    pub transaction_index: u64,
+    // 0 for value of the transaction itself, order of the call in its trace for internal transfers
+    pub position: u64,
+    pub block_timestamp: u64,
+    pub amount: U256,
+}
+
+impl NativeTransfer {
+    pub fn to_transaction(&self, token: &Token) -> Transaction {
+        Transaction {
+            sender: self.sender.clone(),
+            recipient: self.recipient.clone(),
+            tx_hash: self.tx_hash.clone(),
+            block_number: self.block_number,
+            block_hash: self.block_hash.clone(),
+            log_index: self.position,
+            transaction_index: self.transaction_index,
+            operator: None,
+            block_timestamp: Some(self.block_timestamp),
+            receipt: None,
+            transferred_token: TransferredToken::Fungible {
+                address: token.address.clone(),
+                amount: self.amount,
+            },
+        }
+    }
+}
+
+/// Whether an address passes the strategy filter, given the way filters apply to event topics.
+pub fn address_matches(filter: &Option<Vec<H256>>, address: &str) -> bool {
+    let Some(filter) = filter else {
+        return true;
+    };
+    match address.parse::<H160>() {
+        Ok(address) => filter.contains(&H256::from(address)),
+        Err(_) => false,
+    }
+}
+
+/// Scans transactions of every block of the range for non zero value,
+/// keeping succeeded ones whose sender and recipient pass the strategy filters.
+pub async fn fetch_native_transfers(
+    web3: &Web3<RpcTransport>,
+    range: (u64, u64),
+    three_payload_topics: &[Option<Vec<H256>>; 3],
+) -> Result<Vec<NativeTransfer>, IndexerError> {
+    let transport = web3.transport();
+    let block_numbers: Vec<u64> = (range.0..=range.1).collect();
+    let mut transfers = vec![];
+    for batch in block_numbers.chunks(MAX_BLOCKS_PER_BATCH) {
+        let requests: Vec<_> = batch
+            .iter()
+            .map(|block_number| {
+                transport.prepare(
+                    "eth_getBlockByNumber",
+                    vec![Value::String(format!("{:#x}", block_number)), Value::Bool(true)],
+                )
+            })
+            .collect();
+        let responses = match transport.send_batch(requests).await {
+            Ok(responses) => responses,
+            Err(e) => {
+                return Err(IndexerError {
+                    reason: format!("During fetching blocks with transactions occurred {e}"),
+                })
+            }
+        };
+        for (block_number, response) in batch.iter().zip(responses) {
+            let block = match response {
+                Ok(Value::Null) => {
+                    return Err(IndexerError {
+                        reason: format!("Block {block_number} not found on node"),
+                    })
+                }
+                Ok(block) => block,
+                Err(e) => {
+                    return Err(IndexerError {
+                        reason: format!("During fetching block {block_number} occurred {e}"),
+                    })
+                }
+            };
+            let (block_transfers, creations) = parse_block_transfers(*block_number, &block)?;
+            // receipts are fetched only for transfers the filters may keep, recipient of creations is not known yet
+            let candidates: Vec<NativeTransfer> = block_transfers
+                .into_iter()
+                .chain(creations)
+                .filter(|transfer| {
+                    address_matches(&three_payload_topics[0], &transfer.sender)
+                        && (transfer.recipient.is_empty() || address_matches(&three_payload_topics[1], &transfer.recipient))
+                })
+                .collect();
+            for transfer in apply_receipts(web3, candidates).await? {
+                if address_matches(&three_payload_topics[1], &transfer.recipient) {
+                    transfers.push(transfer);
+                }
+            }
+        }
+    }
+    Ok(transfers)
+}
+
+/// Drops transfers of reverted transactions and sets recipient of contract creations,
+/// both known only from receipts.
+async fn apply_receipts(
+    web3: &Web3<RpcTransport>,
+    transfers: Vec<NativeTransfer>,
+) -> Result<Vec<NativeTransfer>, IndexerError> {
+    let transport = web3.transport();
+    let mut receipts = vec![];
+    for batch in transfers.chunks(MAX_RECEIPTS_PER_BATCH) {
+        let requests: Vec<_> = batch
+            .iter()
+            .map(|transfer| transport.prepare("eth_getTransactionReceipt", vec![Value::String(transfer.tx_hash.clone())]))
+            .collect();
+        let responses = match transport.send_batch(requests).await {
+            Ok(responses) => responses,
+            Err(e) => {
+                return Err(IndexerError {
+                    reason: format!("During fetching receipts of native transfers occurred {e}"),
+                })
+            }
+        };
+        for (transfer, response) in batch.iter().zip(responses) {
+            match response {
+                Ok(receipt) => receipts.push(receipt),
+                Err(e) => {
+                    return Err(IndexerError {
+                        reason: format!("During fetching receipt of {} occurred {e}", transfer.tx_hash),
+                    })
+                }
+            }
+        }
+    }
+    let mut applied = vec![];
+    for (transfer, receipt) in transfers.into_iter().zip(receipts) {
+        if let Some(transfer) = apply_receipt(transfer, &receipt)? {
+            applied.push(transfer);
+        }
+    }
+    Ok(applied)
+}
+
+fn apply_receipt(mut transfer: NativeTransfer, receipt: &Value) -> Result<Option<NativeTransfer>, IndexerError> {
+    if receipt.is_null() {
+        return Err(IndexerError {
+            reason: format!("Receipt of {} not found on node", transfer.tx_hash),
+        });
+    }
+    // value of a reverted transaction never moved, receipts before Byzantium have no status
+    if read_u64(receipt, "status") == Some(0) {
+        return Ok(None);
+    }
+    if transfer.recipient.is_empty() {
+        match read_h160(receipt, "contractAddress") {
+            Some(contract_address) => transfer.recipient = bytes20_to_address(&contract_address),
+            None => {
+                return Err(IndexerError {
+                    reason: format!("Receipt of contract creation {} has no contract address", transfer.tx_hash),
+                })
+            }
+        }
+    }
+    Ok(Some(transfer))
+}
+
+/// Reads only the needed fields of the block, so PoA headers with oversized extraData pass too.
+/// Contract creations are returned apart, their recipient is left empty until read from receipts.
+fn parse_block_transfers(
+    block_number: u64,
+    block: &Value,
+) -> Result<(Vec<NativeTransfer>, Vec<NativeTransfer>), IndexerError> {
+    let bad_block = |field: &str| IndexerError {
+        reason: format!("Block {block_number} has no valid {field}"),
+    };
+    let block_hash = read_h256(block, "hash").ok_or_else(|| bad_block("hash"))?;
+    let block_timestamp = read_u64(block, "timestamp").ok_or_else(|| bad_block("timestamp"))?;
+    let Some(transactions) = block.get("transactions").and_then(|transactions| transactions.as_array()) else {
+        return Err(bad_block("transactions"));
+    };
+    let mut transfers = vec![];
+    let mut creations = vec![];
+    for transaction in transactions.iter() {
+        let amount = read_u256(transaction, "value").ok_or_else(|| bad_block("transaction value"))?;
+        if amount.is_zero() {
+            continue;
+        }
+        let recipient = read_h160(transaction, "to");
+        let sender = read_h160(transaction, "from").ok_or_else(|| bad_block("transaction sender"))?;
+        let tx_hash = read_h256(transaction, "hash").ok_or_else(|| bad_block("transaction hash"))?;
+        let transaction_index = read_u64(transaction, "transactionIndex").ok_or_else(|| bad_block("transaction index"))?;
+        let transfer = NativeTransfer {
+            sender: bytes20_to_address(&sender),
+            recipient: recipient.map_or(String::new(), |recipient| bytes20_to_address(&recipient)),
+            tx_hash: bytes32_to_string(&tx_hash),
+            block_number,
+            block_hash: bytes32_to_string(&block_hash),
+            transaction_index,
+            position: 0,
+            block_timestamp,
+            amount,
+        };
+        // value sent with contract creation lands on an address known only from the receipt
+        match recipient {
+            Some(_) => transfers.push(transfer),
+            None => creations.push(transfer),
+        }
+    }
+    Ok((transfers, creations))
+}
+
+pub fn read_h256(value: &Value, key: &str) -> Option<H256> {
+    serde_json::from_value::<H256>(value.get(key)?.clone()).ok()
+}
+
+pub fn read_h160(value: &Value, key: &str) -> Option<H160> {
+    serde_json::from_value::<H160>(value.get(key)?.clone()).ok()
+}
+
+pub fn read_u256(value: &Value, key: &str) -> Option<U256> {
+    serde_json::from_value::<U256>(value.get(key)?.clone()).ok()
+}
+
+pub fn read_u64(value: &Value, key: &str) -> Option<u64> {
+    serde_json::from_value::<U64>(value.get(key)?.clone()).ok().map(|number| number.as_u64())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use web3::types::U256;
+
+    use super::{apply_receipt, parse_block_transfers};
+
+    #[test]
+    fn separates_contract_creations_and_skips_zero_value() {
+        let block = json!({
+            "hash": "0x5e8f6b2e4c7c0a6d5c6bd3d2c8b5e8c1f0c3a2a1d7e6c5b4a3f2e1d0c9b8a796",
+            "timestamp": "0x6553f100",
+            "transactions": [
+                {
+                    "hash": "0x1111111111111111111111111111111111111111111111111111111111111111",
+                    "from": "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48",
+                    "to": "0xdac17f958d2ee523a2206206994597c13d831ec7",
+                    "value": "0xde0b6b3a7640000",
+                    "transactionIndex": "0x0"
+                },
+                {
+                    "hash": "0x2222222222222222222222222222222222222222222222222222222222222222",
+                    "from": "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48",
+                    "to": null,
+                    "value": "0x1",
+                    "transactionIndex": "0x1"
+                },
+                {
+                    "hash": "0x3333333333333333333333333333333333333333333333333333333333333333",
+                    "from": "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48",
+                    "to": "0xdac17f958d2ee523a2206206994597c13d831ec7",
+                    "value": "0x0",
+                    "transactionIndex": "0x2"
+                }
+            ]
+        });
+        let (transfers, creations) = parse_block_transfers(100, &block).unwrap();
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].recipient, "0xdac17f958d2ee523a2206206994597c13d831ec7");
+        assert_eq!(transfers[0].amount, U256::exp10(18));
+        assert_eq!(creations.len(), 1);
+        assert_eq!(creations[0].transaction_index, 1);
+        assert!(creations[0].recipient.is_empty());
+    }
+
+    #[test]
+    fn drops_reverted_transfers_and_fills_created_contracts() {
+        let block = json!({
+            "hash": "0x5e8f6b2e4c7c0a6d5c6bd3d2c8b5e8c1f0c3a2a1d7e6c5b4a3f2e1d0c9b8a796",
+            "timestamp": "0x6553f100",
+            "transactions": [
+                {
+                    "hash": "0x1111111111111111111111111111111111111111111111111111111111111111",
+                    "from": "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48",
+                    "to": "0xdac17f958d2ee523a2206206994597c13d831ec7",
+                    "value": "0x1",
+                    "transactionIndex": "0x0"
+                },
+                {
+                    "hash": "0x2222222222222222222222222222222222222222222222222222222222222222",
+                    "from": "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48",
+                    "to": null,
+                    "value": "0x1",
+                    "transactionIndex": "0x1"
+                }
+            ]
+        });
+        let (mut transfers, mut creations) = parse_block_transfers(100, &block).unwrap();
+
+        let reverted = json!({"status": "0x0", "contractAddress": null});
+        assert!(apply_receipt(transfers.remove(0), &reverted).unwrap().is_none());
+
+        let created = json!({"status": "0x1", "contractAddress": "0x5f4ec3df9cbd43714fe2740f5e3616155c5b8419"});
+        let creation = apply_receipt(creations.remove(0), &created).unwrap().unwrap();
+        assert_eq!(creation.recipient, "0x5f4ec3df9cbd43714fe2740f5e3616155c5b8419");
+    }
+
+    #[test]
+    fn keeps_transfers_of_receipts_without_status() {
+        let block = json!({
+            "hash": "0x5e8f6b2e4c7c0a6d5c6bd3d2c8b5e8c1f0c3a2a1d7e6c5b4a3f2e1d0c9b8a796",
+            "timestamp": "0x55ba467c",
+            "transactions": [{
+                "hash": "0x1111111111111111111111111111111111111111111111111111111111111111",
+                "from": "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48",
+                "to": "0xdac17f958d2ee523a2206206994597c13d831ec7",
+                "value": "0x1",
+                "transactionIndex": "0x0"
+            }]
+        });
+        let (mut transfers, _) = parse_block_transfers(100, &block).unwrap();
+        let pre_byzantium = json!({
+            "root": "0x5e8f6b2e4c7c0a6d5c6bd3d2c8b5e8c1f0c3a2a1d7e6c5b4a3f2e1d0c9b8a796",
+            "contractAddress": null
+        });
+        assert!(apply_receipt(transfers.remove(0), &pre_byzantium).unwrap().is_some());
+        assert!(apply_receipt(parse_block_transfers(100, &block).unwrap().0.remove(0), &json!(null)).is_err());
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+
+// compute units per call, heavier calls eat more of the provider budget
+const METHOD_WEIGHTS: &[(&str, u32)] = &[
+    ("eth_blockNumber", 10),
+    ("eth_chainId", 0),
+    ("eth_getBlockByNumber", 16),
+    ("eth_getBlockByHash", 16),
+    ("eth_getLogs", 75),
+    ("eth_newFilter", 20),
+    ("eth_getFilterLogs", 75),
+    ("eth_uninstallFilter", 10),
+    ("eth_getTransactionReceipt", 15),
+    ("eth_getTransactionByHash", 17),
+    ("eth_subscribe", 10),
+    // traces replay every transaction of the block, providers charge them the most
+    ("trace_block", 500),
+    ("debug_traceBlockByNumber", 500),
+];
+const DEFAULT_METHOD_WEIGHT: u32 = 20;
+const USAGE_REPORT_SECONDS: u64 = 60;
+const MAX_PAUSE_SECONDS: u64 = 300;
+
+pub fn method_weight(method: &str) -> u32 {
+    METHOD_WEIGHTS
+        .iter()
+        .find(|(name, _)| *name == method)
+        .map_or(DEFAULT_METHOD_WEIGHT, |(_, weight)| *weight)
+}
+
+#[derive(Debug)]
+struct LimiterState {
+    requests_per_second: Option<f64>,
+    compute_units_per_second: Option<f64>,
+    request_tokens: f64,
+    unit_tokens: f64,
+    refilled_at: Instant,
+    paused_until: Option<Instant>,
+    window_started: Instant,
+    window_requests: u64,
+    window_units: u64,
+}
+
+impl LimiterState {
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.refilled_at = now;
+        // buckets hold at most one second of budget
+        if let Some(rps) = self.requests_per_second {
+            self.request_tokens = (self.request_tokens + elapsed * rps).min(rps.max(1.0));
+        }
+        if let Some(cups) = self.compute_units_per_second {
+            self.unit_tokens = (self.unit_tokens + elapsed * cups).min(cups);
+        }
+    }
+
+    /// Time to wait until a call of `weight` fits into the budget.
+    fn wait_for(&self, weight: u32) -> Duration {
+        let mut wait: f64 = 0.0;
+        if let Some(rps) = self.requests_per_second {
+            if self.request_tokens < 1.0 {
+                wait = wait.max((1.0 - self.request_tokens) / rps);
+            }
+        }
+        if let Some(cups) = self.compute_units_per_second {
+            // calls heavier than the whole bucket go through once it is full, leaving debt
+            let needed = (weight as f64).min(cups);
+            if self.unit_tokens < needed {
+                wait = wait.max((needed - self.unit_tokens) / cups);
+            }
+        }
+        Duration::from_secs_f64(wait)
+    }
+
+    fn usage_report(&self, elapsed: Duration) -> String {
+        let seconds = elapsed.as_secs_f64();
+        let requests = match self.requests_per_second {
+            Some(rps) => format!("{:.0}% of {rps} requests/s", self.window_requests as f64 / seconds / rps * 100.0),
+            None => format!("{:.1} requests/s unlimited", self.window_requests as f64 / seconds),
+        };
+        let units = match self.compute_units_per_second {
+            Some(cups) => format!("{:.0}% of {cups} compute units/s", self.window_units as f64 / seconds / cups * 100.0),
+            None => format!("{:.1} compute units/s unlimited", self.window_units as f64 / seconds),
+        };
+        format!("{requests}, {units}")
+    }
+}
+
+/// Token buckets for requests and compute units of one network,
+/// shared by every indexer of the process using that network.
+#[derive(Debug)]
+pub struct RateLimiter {
+    network_name: String,
+    state: Mutex<LimiterState>,
+}
+
+impl RateLimiter {
+    pub fn new(network_name: &str, requests_per_second: Option<f64>, compute_units_per_second: Option<f64>) -> Self {
+        let now = Instant::now();
+        Self {
+            network_name: network_name.to_string(),
+            state: Mutex::new(LimiterState {
+                requests_per_second,
+                compute_units_per_second,
+                request_tokens: requests_per_second.unwrap_or(0.0).max(1.0),
+                unit_tokens: compute_units_per_second.unwrap_or(0.0),
+                refilled_at: now,
+                paused_until: None,
+                window_started: now,
+                window_requests: 0,
+                window_units: 0,
+            }),
+        }
+    }
+
+    pub fn network_name(&self) -> &str {
+        &self.network_name
+    }
+
+    /// Applies limits changed in the database without resetting the pause.
+    pub fn update_limits(&self, requests_per_second: Option<f64>, compute_units_per_second: Option<f64>) {
+        let mut state = self.state.lock().unwrap();
+        if state.requests_per_second != requests_per_second || state.compute_units_per_second != compute_units_per_second {
+            info!(
+                "Rate limits of network {} changed to {requests_per_second:?} requests/s and {compute_units_per_second:?} compute units/s",
+                self.network_name
+            );
+            state.requests_per_second = requests_per_second;
+            state.compute_units_per_second = compute_units_per_second;
+            state.request_tokens = requests_per_second.unwrap_or(0.0).max(1.0);
+            state.unit_tokens = compute_units_per_second.unwrap_or(0.0);
+        }
+    }
+
+    /// Waits until the budget allows calling `method`, then takes its cost from the budget.
+    pub async fn acquire(&self, method: &str) {
+        let weight = method_weight(method);
+        loop {
+            match self.try_acquire(weight) {
+                Some(wait) => tokio::time::sleep(wait).await,
+                None => return,
+            }
+        }
+    }
+
+    fn try_acquire(&self, weight: u32) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        state.refill(now);
+        if let Some(paused_until) = state.paused_until {
+            if paused_until > now {
+                return Some(paused_until - now);
+            }
+            state.paused_until = None;
+        }
+        let wait = state.wait_for(weight);
+        if !wait.is_zero() {
+            return Some(wait);
+        }
+        state.request_tokens -= 1.0;
+        state.unit_tokens -= weight as f64;
+        state.window_requests += 1;
+        state.window_units += weight as u64;
+        let elapsed = now.duration_since(state.window_started);
+        if elapsed >= Duration::from_secs(USAGE_REPORT_SECONDS) {
+            info!("RPC budget of network {} used: {}", self.network_name, state.usage_report(elapsed));
+            state.window_started = now;
+            state.window_requests = 0;
+            state.window_units = 0;
+        }
+        None
+    }
+
+    /// Holds every call of the network back, e.g. for `Retry-After` of a 429 response.
+    pub fn pause_for(&self, duration: Duration) {
+        let duration = duration.min(Duration::from_secs(MAX_PAUSE_SECONDS));
+        let until = Instant::now() + duration;
+        let mut state = self.state.lock().unwrap();
+        if state.paused_until.is_none_or(|paused_until| paused_until < until) {
+            warn!("Network {} is rate limited, pausing calls for {}s", self.network_name, duration.as_secs_f64());
+            state.paused_until = Some(until);
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+use web3::{
+    types::{H160, H256, U256, U64},
+    BatchTransport, Transport, Web3,
+};
+
+use super::{
+    byte_parsers::{bytes20_to_address, bytes32_to_string},
+    commons::IndexerError,
+    transactions::ReceiptDetails,
+    transports::RpcTransport,
+};
+
+// each transaction takes two calls of the batch
+const MAX_TRANSACTIONS_PER_BATCH: usize = 50;
+
+/// Fetches transaction and receipt of every hash with batched calls, keyed by tx hash.
+pub async fn fetch_receipt_details(
+    web3: &Web3<RpcTransport>,
+    tx_hashes: &[H256],
+) -> Result<HashMap<String, ReceiptDetails>, IndexerError> {
+    let transport = web3.transport();
+    let mut details = HashMap::new();
+    for batch in tx_hashes.chunks(MAX_TRANSACTIONS_PER_BATCH) {
+        let mut requests = vec![];
+        for tx_hash in batch.iter() {
+            let params = vec![Value::String(bytes32_to_string(tx_hash))];
+            requests.push(transport.prepare("eth_getTransactionByHash", params.clone()));
+            requests.push(transport.prepare("eth_getTransactionReceipt", params));
+        }
+        let responses = match transport.send_batch(requests).await {
+            Ok(responses) => responses,
+            Err(e) => {
+                return Err(IndexerError {
+                    reason: format!("During fetching transaction receipts occurred {e}"),
+                })
+            }
+        };
+        for (tx_hash, pair) in batch.iter().zip(responses.chunks(2)) {
+            let tx_hash = bytes32_to_string(tx_hash);
+            match (&pair[0], &pair[1]) {
+                (Ok(transaction), Ok(receipt)) if !transaction.is_null() && !receipt.is_null() => {
+                    details.insert(tx_hash, parse_receipt_details(transaction, receipt)?);
+                }
+                (Err(e), _) | (_, Err(e)) => {
+                    return Err(IndexerError {
+                        reason: format!("During fetching transaction {tx_hash} occurred {e}"),
+                    })
+                }
+                _ => {
+                    return Err(IndexerError {
+                        reason: format!("Transaction {tx_hash} or its receipt not found on node"),
+                    })
+                }
+            }
+        }
+    }
+    Ok(details)
+}
+
+/// Reads only the needed fields, so chains with non standard transaction types are handled too.
+fn parse_receipt_details(transaction: &Value, receipt: &Value) -> Result<ReceiptDetails, IndexerError> {
+    let origin = match transaction.get("from").map(|from| serde_json::from_value::<H160>(from.clone())) {
+        Some(Ok(from)) => bytes20_to_address(&from),
+        _ => {
+            return Err(IndexerError {
+                reason: String::from("Transaction has no sender"),
+            })
+        }
+    };
+    // receipts before London have no effective gas price, legacy gas price was paid then
+    let effective_gas_price = read_u256(receipt, "effectiveGasPrice").or(read_u256(transaction, "gasPrice"));
+    Ok(ReceiptDetails {
+        origin,
+        gas_used: read_u256(receipt, "gasUsed"),
+        effective_gas_price,
+        status: read_u64(receipt, "status"),
+    })
+}
+
+fn read_u256(value: &Value, key: &str) -> Option<U256> {
+    serde_json::from_value::<U256>(value.get(key)?.clone()).ok()
+}
+
+fn read_u64(value: &Value, key: &str) -> Option<u64> {
+    serde_json::from_value::<U64>(value.get(key)?.clone()).ok().map(|number| number.as_u64())
+}
@@ -0,0 +1,123 @@
+use log::{info, warn};
+use tokio_postgres::Client;
+use web3::{
+    types::{BlockId, BlockNumber, U64},
+    Web3,
+};
+
+use crate::models::{BackfillChunk, BlockHash, Indexer, TokenTransfer};
+
+use super::{
+    byte_parsers::bytes32_to_string,
+    commons::{CycleError, IndexerError},
+    transports::RpcTransport,
+};
+
+// how many recorded block hashes are kept and walked back through on reorg
+pub const MAX_REORG_DEPTH: u64 = 256;
+
+pub async fn get_block_hash(web3: &Web3<RpcTransport>, block_number: u64) -> Result<String, IndexerError> {
+    let block_id = BlockId::Number(BlockNumber::Number(U64::from(block_number)));
+    match web3.eth().block(block_id).await {
+        Ok(Some(block)) => match block.hash {
+            Some(hash) => Ok(bytes32_to_string(&hash)),
+            None => Err(IndexerError {
+                reason: format!("Block {block_number} has no hash yet"),
+            }),
+        },
+        Ok(None) => Err(IndexerError {
+            reason: format!("Block {block_number} not found on node"),
+        }),
+        Err(e) => Err(IndexerError {
+            reason: format!("During fetching block {block_number} occurred {e}"),
+        }),
+    }
+}
+
+/// Compares recorded block hashes with the chain starting from the newest one.
+/// Returns the common ancestor block if the newest recorded block was reorged.
+/// A reorg deeper than the recorded hashes cannot be rolled back, so it is fatal.
+pub async fn find_common_ancestor(
+    client: &mut Client,
+    indexer: &Indexer,
+    web3: &Web3<RpcTransport>,
+) -> Result<Option<u64>, CycleError> {
+    let recorded = match BlockHash::load_latest_from_db(client, indexer, MAX_REORG_DEPTH).await {
+        Ok(recorded) => recorded,
+        Err(e) => {
+            return Err(CycleError::Transient(IndexerError {
+                reason: format!("During loading recorded block hashes occurred {}", e.reason),
+            }))
+        }
+    };
+    for (i, block_hash) in recorded.iter().enumerate() {
+        let chain_hash = get_block_hash(web3, block_hash.block_number).await?;
+        if chain_hash.to_lowercase() == block_hash.block_hash.to_lowercase() {
+            if i == 0 {
+                return Ok(None);
+            }
+            return Ok(Some(block_hash.block_number));
+        }
+        warn!(
+            "Block {} was reorged: recorded hash {}, chain hash {chain_hash}",
+            block_hash.block_number, block_hash.block_hash
+        );
+    }
+    match recorded.last() {
+        Some(oldest) => Err(CycleError::Fatal(IndexerError {
+            reason: format!(
+                "Reorg is deeper than {} recorded blocks, oldest checked block is {}",
+                recorded.len(),
+                oldest.block_number
+            ),
+        })),
+        None => Ok(None),
+    }
+}
+
+/// Deletes transfers and block hashes above `ancestor` and moves indexer back to it.
+pub async fn rollback(client: &mut Client, indexer: &mut Indexer, ancestor: u64) -> Result<(), IndexerError> {
+    let mut db_tx = match client.transaction().await {
+        Ok(db_tx) => db_tx,
+        Err(e) => {
+            return Err(IndexerError {
+                reason: format!("During preparing rollback transaction occurred {e}"),
+            })
+        }
+    };
+    let deleted = add_rollback_to_db_tx(&mut db_tx, indexer, ancestor).await?;
+    if let Err(e) = db_tx.commit().await {
+        return Err(IndexerError {
+            reason: format!("During commit of rollback occurred {e}"),
+        });
+    }
+    info!(
+        "Rolled back indexer {} from block {} to {ancestor}, deleted {deleted} orphaned token transfers",
+        indexer.name, indexer.last_block
+    );
+    indexer.last_block = ancestor;
+    Ok(())
+}
+
+/// Adds rollback to `ancestor` to a database transaction, returns the number of deleted transfers.
+pub async fn add_rollback_to_db_tx(
+    db_tx: &mut tokio_postgres::Transaction<'_>,
+    indexer: &Indexer,
+    ancestor: u64,
+) -> Result<u64, IndexerError> {
+    let deleted = match TokenTransfer::delete_after_block(db_tx, indexer, ancestor).await {
+        Ok(deleted) => deleted,
+        Err(e) => return Err(IndexerError { reason: e.reason }),
+    };
+    if let Err(e) = BlockHash::delete_after_block(db_tx, indexer, ancestor).await {
+        return Err(IndexerError { reason: e.reason });
+    }
+    // chunks after the ancestor lost their transfers, so backfill must redo them
+    if let Err(e) = BackfillChunk::delete_after_block(db_tx, indexer, ancestor).await {
+        return Err(IndexerError { reason: e.reason });
+    }
+    if let Err(e) = indexer.add_last_block_to_db_tx(db_tx, ancestor).await {
+        return Err(IndexerError { reason: e.reason });
+    }
+    Ok(deleted)
+}
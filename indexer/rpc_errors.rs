@@ -0,0 +1,129 @@
+use web3::{error::TransportError, Error};
+
+// JSON-RPC code for calls the node does not implement
+const METHOD_NOT_FOUND_CODE: i64 = -32601;
+
+const FILTER_NOT_FOUND_MESSAGES: &[&str] = &["filter not found", "filter does not exist"];
+const LIMIT_EXCEEDED_MESSAGES: &[&str] = &[
+    "query returned more than",
+    "block range too large",
+    "block range is too wide",
+    "block range is too large",
+    "exceed maximum block range",
+    "range too large",
+    "too many results",
+    "log response size exceeded",
+    "response size should not greater than",
+    "block range limit exceeded",
+    "eth_getlogs is limited to",
+];
+const RATE_LIMITED_MESSAGES: &[&str] = &[
+    "too many requests",
+    "rate limit",
+    "request rate exceeded",
+    "compute units per second",
+];
+const METHOD_NOT_SUPPORTED_MESSAGES: &[&str] = &[
+    "method not found",
+    "does not exist/is not available",
+    "not supported",
+];
+
+fn message_matches(error: &Error, messages: &[&str]) -> bool {
+    let message = error.to_string().to_lowercase();
+    messages.iter().any(|shape| message.contains(shape))
+}
+
+/// Stateful filter was lost, e.g. request hit another node behind a round-robin proxy.
+pub fn is_filter_not_found(error: &Error) -> bool {
+    message_matches(error, FILTER_NOT_FOUND_MESSAGES)
+}
+
+/// Provider refused logs query because of result count, block range or response size.
+pub fn is_limit_exceeded(error: &Error) -> bool {
+    !is_rate_limited(error) && message_matches(error, LIMIT_EXCEEDED_MESSAGES)
+}
+
+/// Provider throttled the caller, the same call succeeds later.
+pub fn is_rate_limited(error: &Error) -> bool {
+    matches!(error, Error::Transport(TransportError::Code(429))) || message_matches(error, RATE_LIMITED_MESSAGES)
+}
+
+pub fn is_method_not_supported(error: &Error) -> bool {
+    if let Error::Rpc(rpc_error) = error {
+        if rpc_error.code.code() == METHOD_NOT_FOUND_CODE {
+            return true;
+        }
+    }
+    message_matches(error, METHOD_NOT_SUPPORTED_MESSAGES)
+}
+
+/// Endpoint itself failed to answer, so the same call may succeed on another endpoint.
+pub fn is_endpoint_failure(error: &Error) -> bool {
+    !is_rate_limited(error)
+        && matches!(
+        error,
+        Error::Unreachable | Error::Transport(_) | Error::Io(_) | Error::InvalidResponse(_)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonrpc_core::types::error::{Error as RpcError, ErrorCode};
+    use web3::{error::TransportError, Error};
+
+    use super::{is_limit_exceeded, is_rate_limited};
+
+    fn rpc_error(code: i64, message: &str) -> Error {
+        Error::Rpc(RpcError {
+            code: ErrorCode::ServerError(code),
+            message: String::from(message),
+            data: None,
+        })
+    }
+
+    #[test]
+    fn recognizes_provider_range_limits() {
+        let messages = [
+            // Infura
+            (-32005, "query returned more than 10000 results"),
+            // Alchemy
+            (-32602, "Log response size exceeded. You can make eth_getLogs requests with up to a 2K block range and no limit on the response size, or you can request any block range with a cap of 10K logs in the response."),
+            // QuickNode
+            (-32614, "eth_getLogs is limited to a 10,000 range"),
+            // Ankr
+            (-32600, "block range is too wide"),
+            // BSC
+            (-32000, "exceed maximum block range: 5000"),
+            // Chainstack
+            (-32000, "Block range limit exceeded."),
+            // Cloudflare
+            (-32047, "block range too large"),
+        ];
+        for (code, message) in messages {
+            assert!(is_limit_exceeded(&rpc_error(code, message)), "{message}");
+        }
+    }
+
+    #[test]
+    fn does_not_shrink_range_on_rate_limits_and_timeouts() {
+        let messages = [
+            (-32005, "daily request limit exceeded"),
+            (-32005, "rate limit exceeded"),
+            (429, "Your app has exceeded its compute units per second capacity"),
+            (-32000, "query timeout exceeded"),
+            (-32000, "execution reverted"),
+        ];
+        for (code, message) in messages {
+            assert!(!is_limit_exceeded(&rpc_error(code, message)), "{message}");
+        }
+        assert!(!is_limit_exceeded(&Error::Transport(TransportError::Code(429))));
+    }
+
+    #[test]
+    fn recognizes_rate_limits() {
+        assert!(is_rate_limited(&Error::Transport(TransportError::Code(429))));
+        assert!(is_rate_limited(&rpc_error(-32005, "rate limit exceeded")));
+        assert!(!is_rate_limited(&rpc_error(-32005, "query returned more than 10000 results")));
+    }
+}
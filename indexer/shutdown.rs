@@ -0,0 +1,56 @@
+use log::{error, info, warn};
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::watch,
+};
+
+/// How a run of the indexer ended, reported to the orchestrator through the exit code.
+#[derive(PartialEq)]
+pub enum ExitStatus {
+    // end block reached, stopped from database or shut down by signal with the last range committed
+    Completed,
+    // fatal error or broken configuration, restarting without a fix will not help
+    Failed,
+    // command line could not be understood
+    BadUsage,
+}
+
+impl ExitStatus {
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::Completed => 0,
+            Self::Failed => 1,
+            Self::BadUsage => 2,
+        }
+    }
+}
+
+/// Receiver turning true on the first SIGINT or SIGTERM, so cycles stop after the range being written.
+/// A second signal exits at once, which is still safe as every range is committed in one transaction.
+pub fn listen_for_signals() -> watch::Receiver<bool> {
+    let (sender, receiver) = watch::channel(false);
+    let (mut interrupt, mut terminate) = match (signal(SignalKind::interrupt()), signal(SignalKind::terminate())) {
+        (Ok(interrupt), Ok(terminate)) => (interrupt, terminate),
+        (Err(e), _) | (_, Err(e)) => {
+            warn!("Signal handlers could not be installed, shutdown will not be graceful: {e}");
+            return receiver;
+        }
+    };
+    tokio::spawn(async move {
+        let mut received = 0;
+        loop {
+            let code = tokio::select! {
+                _ = interrupt.recv() => 130,
+                _ = terminate.recv() => 143,
+            };
+            received += 1;
+            if received > 1 {
+                error!("Second signal received, exiting without waiting for the current range");
+                std::process::exit(code);
+            }
+            info!("Shutdown requested, finishing the current range");
+            let _ = sender.send(true);
+        }
+    });
+    receiver
+}
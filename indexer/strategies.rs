@@ -18,7 +18,7 @@ fn get_strategy_params(strategy_params: Option<Value>) -> Result<Value, IndexerE
 }
 
 fn get_address(strategy_json: Value, key: &'static str) -> Result<String, IndexerError> {
-    if let Some(value) = strategy_json.get("recipient") {
+    if let Some(value) = strategy_json.get(key) {
         if value.is_string() {
             let mut address = value.to_string();
             if address.starts_with('"') {
@@ -35,11 +35,13 @@ fn get_address(strategy_json: Value, key: &'static str) -> Result<String, Indexe
         }
     } else {
         Err(IndexerError {
-            reason: format!("Expected {key} key containing recipient address not found"),
+            reason: format!("Expected {key} key containing address not found"),
         })
     }
 }
 
+/// Topics follow the Transfer(from, to, ...) layout of ERC20 and ERC721,
+/// queries of ERC1155 tokens move them one topic further, past the operator.
 pub trait Strategy {
     fn get_payload_topics(
         &self,
@@ -48,7 +50,7 @@ pub trait Strategy {
 }
 
 struct RecipientStrategy();
-const RECIPIENT_KEY: &'static str = "recipient";
+const RECIPIENT_KEY: &str = "recipient";
 impl Strategy for RecipientStrategy {
     fn get_payload_topics(
         &self,
@@ -62,7 +64,7 @@ impl Strategy for RecipientStrategy {
 }
 
 struct SenderStrategy();
-const SENDER_KEY: &'static str = "sender";
+const SENDER_KEY: &str = "sender";
 impl Strategy for SenderStrategy {
     fn get_payload_topics(
         &self,
@@ -71,7 +73,7 @@ impl Strategy for SenderStrategy {
         let strategy_json = get_strategy_params(strategy_params)?;
         let sender = get_address(strategy_json, SENDER_KEY)?;
         let sender_hex = hex_string_to_bytes32(&sender)?;
-        Ok([None, Some(vec![sender_hex]), None])
+        Ok([Some(vec![sender_hex]), None, None])
     }
 }
 
@@ -0,0 +1,78 @@
+use futures::StreamExt;
+use log::{info, warn};
+use web3::{api::SubscriptionStream, transports::WebSocket, types::BlockHeader};
+
+use super::transports::RpcTransport;
+
+/// `newHeads` subscription which wakes the indexer as soon as a block is mined.
+/// Ranges are still fetched by polling, so a lost subscription only delays indexing.
+pub struct HeadSubscription {
+    stream: Option<SubscriptionStream<WebSocket, BlockHeader>>,
+    // newest block announced by the subscription, unconfirmed like every announced block
+    last_head: Option<u64>,
+}
+
+impl HeadSubscription {
+    pub fn new() -> Self {
+        Self {
+            stream: None,
+            last_head: None,
+        }
+    }
+
+    pub fn is_live(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Subscribes if preferred endpoint is a WebSocket.
+    pub async fn ensure_subscribed(&mut self, transport: &RpcTransport) {
+        let Some(websocket) = transport.websocket() else {
+            self.stream = None;
+            return;
+        };
+        if self.stream.is_some() {
+            return;
+        }
+        let web3 = web3::Web3::new(websocket);
+        match web3.eth_subscribe().subscribe_new_heads().await {
+            Ok(stream) => {
+                info!("Subscribed to new heads");
+                // polled head is confirmed or tagged, so gaps are told only between announced blocks
+                self.last_head = None;
+                self.stream = Some(stream);
+            }
+            Err(e) => warn!("On subscribing to new heads occurred {e}, polling instead"),
+        }
+    }
+
+    /// Waits for the next head. Returns None once subscription is lost.
+    pub async fn next_head(&mut self) -> Option<u64> {
+        let stream = self.stream.as_mut()?;
+        match stream.next().await {
+            Some(Ok(header)) => {
+                let head = header.number.map(|number| number.as_u64());
+                if let Some(head) = head {
+                    if let Some(last_head) = self.last_head {
+                        if head > last_head + 1 {
+                            warn!(
+                                "Gap between announced blocks {last_head} and {head}, catching up by polling"
+                            );
+                        }
+                    }
+                    self.last_head = Some(self.last_head.map_or(head, |last_head| std::cmp::max(head, last_head)));
+                }
+                head
+            }
+            Some(Err(e)) => {
+                warn!("New heads subscription failed with {e}, falling back to polling");
+                self.stream = None;
+                None
+            }
+            None => {
+                warn!("New heads subscription was closed, falling back to polling");
+                self.stream = None;
+                None
+            }
+        }
+    }
+}
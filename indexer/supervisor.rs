@@ -0,0 +1,129 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use log::{error, info, warn};
+use tokio::{sync::watch, task::JoinHandle};
+use tokio_postgres::Client;
+
+use crate::{
+    init_db_client,
+    models::{migrate, Indexer, IndexerStatus},
+};
+
+use super::{cycle::indexer_cycle, shutdown::ExitStatus, transports::TransportPool};
+
+// how often the indexers table is polled for added and removed indexers
+const SUPERVISOR_POLL_SECONDS: u64 = 30;
+
+struct Worker {
+    stop: watch::Sender<bool>,
+    handle: JoinHandle<ExitStatus>,
+}
+
+/// Runs every active indexer in its own task, sharing RPC transports per network.
+pub async fn supervise(client: &mut Client, mut shutdown: watch::Receiver<bool>) -> ExitStatus {
+    if let Err(e) = migrate(client).await {
+        warn!("On migrating database occurred {}", e.reason);
+        return ExitStatus::Failed;
+    }
+    let transports = Arc::new(TransportPool::new());
+    let mut workers: HashMap<String, Worker> = HashMap::new();
+    // indexers stopped by a fatal error, restarted once operators change their status
+    let mut failed: HashSet<String> = HashSet::new();
+    while !*shutdown.borrow() {
+        match Indexer::load_active_from_db(client).await {
+            Ok(indexers) => {
+                reap_finished_workers(&mut workers, &mut failed).await;
+                let active_names: Vec<String> = indexers.iter().map(|i| i.name.clone()).collect();
+                for (name, worker) in workers.iter() {
+                    if !active_names.contains(name) && !*worker.stop.borrow() {
+                        info!("Indexer {name} is no more active, stopping it");
+                        let _ = worker.stop.send(true);
+                    }
+                }
+                for indexer in indexers {
+                    if workers.contains_key(&indexer.name) {
+                        continue;
+                    }
+                    if failed.contains(&indexer.name) {
+                        if indexer.status == IndexerStatus::Error.to_string() {
+                            continue;
+                        }
+                        info!("Status of failed indexer {} changed to {}, restarting it", indexer.name, indexer.status);
+                        failed.remove(&indexer.name);
+                    }
+                    let name = indexer.name.clone();
+                    workers.insert(name, spawn_worker(indexer, transports.clone()));
+                }
+                info!("Supervising {} indexers", workers.len());
+            }
+            Err(e) => {
+                warn!("On loading active indexers occurred {}", e.reason);
+                if client.is_closed() {
+                    match init_db_client().await {
+                        Ok(new_client) => *client = new_client,
+                        Err(e) => warn!("On reconnecting to database occurred {e}"),
+                    }
+                }
+            }
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(SUPERVISOR_POLL_SECONDS)) => {}
+            _ = shutdown.changed() => {}
+        }
+    }
+    info!("Stopping {} indexers", workers.len());
+    for worker in workers.values() {
+        let _ = worker.stop.send(true);
+    }
+    // failure of a single indexer is its own status, only the supervisor itself can fail the process
+    for (name, worker) in workers.drain() {
+        if worker.handle.await.is_err() {
+            error!("Indexer {name} panicked");
+        }
+    }
+    ExitStatus::Completed
+}
+
+fn spawn_worker(mut indexer: Indexer, transports: Arc<TransportPool>) -> Worker {
+    let (stop, stop_receiver) = watch::channel(false);
+    let handle = tokio::spawn(async move {
+        // every indexer owns a connection so slow writes of one do not block others
+        match init_db_client().await {
+            Ok(mut client) => {
+                info!("Starting indexer {indexer}");
+                indexer_cycle(&mut client, &mut indexer, &transports, stop_receiver).await
+            }
+            Err(e) => {
+                warn!("Indexer {} could not connect to database: {e}", indexer.name);
+                ExitStatus::Failed
+            }
+        }
+    });
+    Worker { stop, handle }
+}
+
+/// Drops workers whose task ended so that still active indexers are restarted on next poll.
+/// Indexers which failed are remembered, as restarting them would fail the same way.
+async fn reap_finished_workers(workers: &mut HashMap<String, Worker>, failed: &mut HashSet<String>) {
+    let finished: Vec<String> = workers
+        .iter()
+        .filter(|(_, worker)| worker.handle.is_finished())
+        .map(|(name, _)| name.clone())
+        .collect();
+    for name in finished {
+        if let Some(worker) = workers.remove(&name) {
+            match worker.handle.await {
+                Ok(ExitStatus::Failed) => {
+                    warn!("Indexer {name} failed, it is restarted after its status is changed");
+                    failed.insert(name);
+                }
+                Ok(_) => info!("Indexer {name} finished"),
+                Err(_) => error!("Indexer {name} panicked"),
+            }
+        }
+    }
+}
@@ -0,0 +1,459 @@
+use serde_json::{json, Value};
+use web3::{
+    types::{H160, H256, U256},
+    BatchTransport, Transport, Web3,
+};
+
+use crate::models::{Indexer, Network, TraceApi};
+
+use super::{
+    byte_parsers::{bytes20_to_address, bytes32_to_string},
+    commons::IndexerError,
+    native::{address_matches, read_h160, read_h256, read_u256, read_u64, NativeTransfer, MAX_BLOCKS_PER_BATCH},
+    transports::RpcTransport,
+};
+
+/// Tracing interface to use for the indexer, when it opted in and its network is trace-capable.
+pub fn get_trace_api(network: &Network, indexer: &Indexer) -> Option<TraceApi> {
+    if indexer.index_internal_transfers {
+        network.trace_api
+    } else {
+        None
+    }
+}
+
+/// Block a trace belongs to, known from its header requested in the same batch.
+struct TracedBlock {
+    number: u64,
+    hash: String,
+    timestamp: u64,
+    tx_hashes: Vec<H256>,
+}
+
+/// A call which moved value inside a transaction, before strategy filters are applied.
+struct InternalCall {
+    tx_hash: H256,
+    transaction_index: u64,
+    position: u64,
+    sender: Option<H160>,
+    recipient: Option<H160>,
+    amount: U256,
+}
+
+/// Extracts value moved by contracts from traces of every block of the range,
+/// keeping transfers whose sender and recipient pass the strategy filters.
+/// Calls reverted on their own or with an enclosing call are skipped.
+pub async fn fetch_internal_transfers(
+    web3: &Web3<RpcTransport>,
+    range: (u64, u64),
+    trace_api: TraceApi,
+    three_payload_topics: &[Option<Vec<H256>>; 3],
+) -> Result<Vec<NativeTransfer>, IndexerError> {
+    let transport = web3.transport();
+    let block_numbers: Vec<u64> = (range.0..=range.1).collect();
+    let mut transfers = vec![];
+    for batch in block_numbers.chunks(MAX_BLOCKS_PER_BATCH) {
+        let mut requests = vec![];
+        for block_number in batch.iter() {
+            let block_tag = Value::String(format!("{:#x}", block_number));
+            requests.push(transport.prepare("eth_getBlockByNumber", vec![block_tag.clone(), Value::Bool(false)]));
+            requests.push(match trace_api {
+                TraceApi::Parity => transport.prepare("trace_block", vec![block_tag]),
+                TraceApi::Geth => transport.prepare(
+                    "debug_traceBlockByNumber",
+                    vec![block_tag, json!({"tracer": "callTracer"})],
+                ),
+            });
+        }
+        let responses = match transport.send_batch(requests).await {
+            Ok(responses) => responses,
+            Err(e) => {
+                return Err(IndexerError {
+                    reason: format!("During fetching traces with {trace_api} api occurred {e}"),
+                })
+            }
+        };
+        for (block_number, pair) in batch.iter().zip(responses.chunks(2)) {
+            let (header, traces) = match (&pair[0], &pair[1]) {
+                (Ok(Value::Null), _) => {
+                    return Err(IndexerError {
+                        reason: format!("Block {block_number} not found on node"),
+                    })
+                }
+                (Ok(header), Ok(traces)) => (header, traces),
+                (Err(e), _) | (_, Err(e)) => {
+                    return Err(IndexerError {
+                        reason: format!("During fetching traces of block {block_number} occurred {e}"),
+                    })
+                }
+            };
+            let block = parse_traced_block(*block_number, header)?;
+            let calls = match trace_api {
+                TraceApi::Parity => parse_parity_traces(&block, traces)?,
+                TraceApi::Geth => parse_geth_traces(&block, traces)?,
+            };
+            for call in calls {
+                // value sent to a failed creation has no recipient
+                let (Some(sender), Some(recipient)) = (call.sender, call.recipient) else {
+                    continue;
+                };
+                let sender = bytes20_to_address(&sender);
+                let recipient = bytes20_to_address(&recipient);
+                if !address_matches(&three_payload_topics[0], &sender)
+                    || !address_matches(&three_payload_topics[1], &recipient)
+                {
+                    continue;
+                }
+                transfers.push(NativeTransfer {
+                    sender,
+                    recipient,
+                    tx_hash: bytes32_to_string(&call.tx_hash),
+                    block_number: block.number,
+                    block_hash: block.hash.clone(),
+                    transaction_index: call.transaction_index,
+                    position: call.position,
+                    block_timestamp: block.timestamp,
+                    amount: call.amount,
+                });
+            }
+        }
+    }
+    Ok(transfers)
+}
+
+fn parse_traced_block(block_number: u64, header: &Value) -> Result<TracedBlock, IndexerError> {
+    let bad_block = |field: &str| IndexerError {
+        reason: format!("Block {block_number} has no valid {field}"),
+    };
+    let hash = read_h256(header, "hash").ok_or_else(|| bad_block("hash"))?;
+    let timestamp = read_u64(header, "timestamp").ok_or_else(|| bad_block("timestamp"))?;
+    let tx_hashes = match header.get("transactions").and_then(|transactions| transactions.as_array()) {
+        Some(transactions) => transactions
+            .iter()
+            .filter_map(|tx_hash| serde_json::from_value::<H256>(tx_hash.clone()).ok())
+            .collect(),
+        None => return Err(bad_block("transactions")),
+    };
+    Ok(TracedBlock {
+        number: block_number,
+        hash: bytes32_to_string(&hash),
+        timestamp,
+        tx_hashes,
+    })
+}
+
+/// Flat traces of trace_block come in depth first order, nesting is told by traceAddress.
+fn parse_parity_traces(block: &TracedBlock, traces: &Value) -> Result<Vec<InternalCall>, IndexerError> {
+    let Some(traces) = traces.as_array() else {
+        return Err(IndexerError {
+            reason: format!("Traces of block {} are not a list", block.number),
+        });
+    };
+    let mut calls = vec![];
+    let mut current_tx: Option<H256> = None;
+    let mut position = 0;
+    let mut reverted: Vec<Vec<u64>> = vec![];
+    for trace in traces.iter() {
+        // block and uncle rewards belong to no transaction
+        let Some(tx_hash) = read_h256(trace, "transactionHash") else {
+            continue;
+        };
+        if current_tx != Some(tx_hash) {
+            current_tx = Some(tx_hash);
+            position = 0;
+            reverted.clear();
+        } else {
+            position += 1;
+        }
+        let trace_address: Vec<u64> = match trace.get("traceAddress").and_then(|address| address.as_array()) {
+            Some(address) => address.iter().filter_map(|index| index.as_u64()).collect(),
+            None => {
+                return Err(IndexerError {
+                    reason: format!("Trace of {tx_hash:?} has no traceAddress"),
+                })
+            }
+        };
+        if trace.get("error").is_some_and(|error| !error.is_null()) {
+            reverted.push(trace_address.clone());
+        }
+        if trace_address.is_empty() || reverted.iter().any(|prefix| trace_address.starts_with(prefix)) {
+            continue;
+        }
+        let Some(action) = trace.get("action") else {
+            continue;
+        };
+        let (sender, recipient, amount) = match trace.get("type").and_then(|kind| kind.as_str()) {
+            Some("call") if action.get("callType").and_then(|kind| kind.as_str()) == Some("call") => {
+                (read_h160(action, "from"), read_h160(action, "to"), read_u256(action, "value"))
+            }
+            Some("create") => (
+                read_h160(action, "from"),
+                trace.get("result").and_then(|result| read_h160(result, "address")),
+                read_u256(action, "value"),
+            ),
+            Some("suicide") | Some("selfdestruct") => (
+                read_h160(action, "address"),
+                read_h160(action, "refundAddress"),
+                read_u256(action, "balance"),
+            ),
+            _ => continue,
+        };
+        let Some(amount) = amount.filter(|amount| !amount.is_zero()) else {
+            continue;
+        };
+        let Some(transaction_index) = trace.get("transactionPosition").and_then(|position| position.as_u64()) else {
+            return Err(IndexerError {
+                reason: format!("Trace of {tx_hash:?} has no transactionPosition"),
+            });
+        };
+        calls.push(InternalCall {
+            tx_hash,
+            transaction_index,
+            position,
+            sender,
+            recipient,
+            amount,
+        });
+    }
+    Ok(calls)
+}
+
+/// callTracer returns a tree of calls per transaction, in order of transactions in the block.
+fn parse_geth_traces(block: &TracedBlock, traces: &Value) -> Result<Vec<InternalCall>, IndexerError> {
+    let Some(traces) = traces.as_array() else {
+        return Err(IndexerError {
+            reason: format!("Traces of block {} are not a list", block.number),
+        });
+    };
+    let mut calls = vec![];
+    for (transaction_index, trace) in traces.iter().enumerate() {
+        // older nodes do not return txHash along with the result
+        let tx_hash = match read_h256(trace, "txHash").or_else(|| block.tx_hashes.get(transaction_index).copied()) {
+            Some(tx_hash) => tx_hash,
+            None => {
+                return Err(IndexerError {
+                    reason: format!("Trace {transaction_index} of block {} has no transaction", block.number),
+                })
+            }
+        };
+        let Some(frame) = trace.get("result") else {
+            return Err(IndexerError {
+                reason: format!("Trace of {tx_hash:?} failed: {}", trace.get("error").unwrap_or(&Value::Null)),
+            });
+        };
+        let mut position = 0;
+        walk_geth_frame(frame, tx_hash, transaction_index as u64, &mut position, false, &mut calls);
+    }
+    Ok(calls)
+}
+
+/// Numbers frames depth first like trace_block does, the top frame being the transaction itself.
+fn walk_geth_frame(
+    frame: &Value,
+    tx_hash: H256,
+    transaction_index: u64,
+    position: &mut u64,
+    reverted: bool,
+    calls: &mut Vec<InternalCall>,
+) {
+    let frame_position = *position;
+    let reverted = reverted || frame.get("error").is_some_and(|error| !error.is_null());
+    if frame_position > 0 && !reverted {
+        let moves_value = matches!(
+            frame.get("type").and_then(|kind| kind.as_str()),
+            Some("CALL") | Some("CREATE") | Some("CREATE2") | Some("SELFDESTRUCT")
+        );
+        if let Some(amount) = read_u256(frame, "value").filter(|amount| moves_value && !amount.is_zero()) {
+            calls.push(InternalCall {
+                tx_hash,
+                transaction_index,
+                position: frame_position,
+                sender: read_h160(frame, "from"),
+                recipient: read_h160(frame, "to"),
+                amount,
+            });
+        }
+    }
+    if let Some(children) = frame.get("calls").and_then(|children| children.as_array()) {
+        for child in children.iter() {
+            *position += 1;
+            walk_geth_frame(child, tx_hash, transaction_index, position, reverted, calls);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use web3::types::{H160, H256, U256};
+
+    use super::{parse_geth_traces, parse_parity_traces, parse_traced_block, InternalCall, TracedBlock};
+
+    const TX_A: &str = "0x1111111111111111111111111111111111111111111111111111111111111111";
+    const TX_B: &str = "0x2222222222222222222222222222222222222222222222222222222222222222";
+    const ROUTER: &str = "0x7a250d5630b4cf539739df2c5dacb4c659f2488d";
+    const WALLET: &str = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+    const LIBRARY: &str = "0xdac17f958d2ee523a2206206994597c13d831ec7";
+    const CREATED: &str = "0x5f4eC3Df9cbd43714FE2740f5E3616155c5b8419";
+
+    fn traced_block() -> TracedBlock {
+        parse_traced_block(
+            18_000_000,
+            &json!({
+                "hash": "0x5e8f6b2e4c7c0a6d5c6bd3d2c8b5e8c1f0c3a2a1d7e6c5b4a3f2e1d0c9b8a796",
+                "timestamp": "0x64f0a1b3",
+                "transactions": [TX_A, TX_B]
+            }),
+        )
+        .unwrap()
+    }
+
+    fn address(value: &str) -> Option<H160> {
+        Some(value.parse().unwrap())
+    }
+
+    // tx hash, transaction index, position, sender, recipient and amount of each call
+    type CallSummary = (H256, u64, u64, Option<H160>, Option<H160>, U256);
+
+    fn summary(calls: &[InternalCall]) -> Vec<CallSummary> {
+        calls
+            .iter()
+            .map(|call| (call.tx_hash, call.transaction_index, call.position, call.sender, call.recipient, call.amount))
+            .collect()
+    }
+
+    fn parity_call(call_type: &str, from: &str, to: &str, value: &str, trace_address: &[u64]) -> serde_json::Value {
+        json!({
+            "action": {"callType": call_type, "from": from, "to": to, "value": value, "gas": "0x2710", "input": "0x"},
+            "result": {"gasUsed": "0x0", "output": "0x"},
+            "subtraces": 0,
+            "traceAddress": trace_address,
+            "transactionHash": TX_A,
+            "transactionPosition": 0,
+            "type": "call"
+        })
+    }
+
+    // fixtures are written by hand in the shape nodes return, trimmed to the fields which are read
+
+    #[test]
+    fn parses_parity_trace_block() {
+        let mut reverted = parity_call("call", ROUTER, LIBRARY, "0x5", &[3]);
+        reverted["error"] = json!("Reverted");
+        reverted["result"] = json!(null);
+        reverted["subtraces"] = json!(1);
+        let traces = json!([
+            parity_call("call", WALLET, ROUTER, "0xde0b6b3a7640000", &[]),
+            parity_call("call", ROUTER, WALLET, "0x6f05b59d3b20000", &[0]),
+            parity_call("call", WALLET, LIBRARY, "0x2", &[0, 0]),
+            parity_call("delegatecall", ROUTER, LIBRARY, "0x6f05b59d3b20000", &[1]),
+            parity_call("staticcall", ROUTER, LIBRARY, "0x0", &[2]),
+            reverted,
+            parity_call("call", LIBRARY, WALLET, "0x7", &[3, 0]),
+            {
+                "action": {"from": ROUTER, "value": "0x3", "gas": "0x2710", "init": "0x60806040"},
+                "result": {"address": CREATED, "code": "0x", "gasUsed": "0x0"},
+                "subtraces": 0,
+                "traceAddress": [4],
+                "transactionHash": TX_A,
+                "transactionPosition": 0,
+                "type": "create"
+            },
+            {
+                "action": {"address": CREATED, "refundAddress": WALLET, "balance": "0x3"},
+                "result": null,
+                "subtraces": 0,
+                "traceAddress": [4, 0],
+                "transactionHash": TX_A,
+                "transactionPosition": 0,
+                "type": "suicide"
+            },
+            {
+                "action": {"author": WALLET, "rewardType": "block", "value": "0x1bc16d674ec80000"},
+                "blockNumber": 18_000_000,
+                "result": null,
+                "subtraces": 0,
+                "traceAddress": [],
+                "type": "reward"
+            }
+        ]);
+
+        let calls = parse_parity_traces(&traced_block(), &traces).unwrap();
+
+        let tx_a: H256 = TX_A.parse().unwrap();
+        assert_eq!(
+            summary(&calls),
+            vec![
+                (tx_a, 0, 1, address(ROUTER), address(WALLET), U256::from(500_000_000_000_000_000u64)),
+                (tx_a, 0, 2, address(WALLET), address(LIBRARY), U256::from(2)),
+                (tx_a, 0, 7, address(ROUTER), address(CREATED), U256::from(3)),
+                (tx_a, 0, 8, address(CREATED), address(WALLET), U256::from(3)),
+            ]
+        );
+    }
+
+    fn geth_frame(kind: &str, from: &str, to: &str, value: Option<&str>, calls: serde_json::Value) -> serde_json::Value {
+        let mut frame = json!({"type": kind, "from": from, "to": to, "gas": "0x2710", "gasUsed": "0x0", "input": "0x", "calls": calls});
+        if let Some(value) = value {
+            frame["value"] = json!(value);
+        }
+        frame
+    }
+
+    #[test]
+    fn parses_geth_call_tracer() {
+        let mut reverted = geth_frame(
+            "CALL",
+            ROUTER,
+            LIBRARY,
+            Some("0x5"),
+            json!([geth_frame("CALL", LIBRARY, WALLET, Some("0x7"), json!([]))]),
+        );
+        reverted["error"] = json!("execution reverted");
+        let top = geth_frame(
+            "CALL",
+            WALLET,
+            ROUTER,
+            Some("0xde0b6b3a7640000"),
+            json!([
+                geth_frame(
+                    "CALL",
+                    ROUTER,
+                    WALLET,
+                    Some("0x6f05b59d3b20000"),
+                    json!([geth_frame("CALL", WALLET, LIBRARY, Some("0x2"), json!([]))]),
+                ),
+                geth_frame("DELEGATECALL", ROUTER, LIBRARY, Some("0x6f05b59d3b20000"), json!([])),
+                geth_frame("STATICCALL", ROUTER, LIBRARY, None, json!([])),
+                reverted,
+                geth_frame(
+                    "CREATE2",
+                    ROUTER,
+                    CREATED,
+                    Some("0x3"),
+                    json!([geth_frame("SELFDESTRUCT", CREATED, WALLET, Some("0x3"), json!([]))]),
+                ),
+            ]),
+        );
+        let traces = json!([
+            {"txHash": TX_A, "result": top},
+            // older geth returns results only, matched to the block's transactions by order
+            {"result": geth_frame("CALL", WALLET, ROUTER, Some("0x0"), json!([geth_frame("CALL", ROUTER, LIBRARY, Some("0x9"), json!([]))]))}
+        ]);
+
+        let calls = parse_geth_traces(&traced_block(), &traces).unwrap();
+
+        let tx_a: H256 = TX_A.parse().unwrap();
+        let tx_b: H256 = TX_B.parse().unwrap();
+        assert_eq!(
+            summary(&calls),
+            vec![
+                (tx_a, 0, 1, address(ROUTER), address(WALLET), U256::from(500_000_000_000_000_000u64)),
+                (tx_a, 0, 2, address(WALLET), address(LIBRARY), U256::from(2)),
+                (tx_a, 0, 7, address(ROUTER), address(CREATED), U256::from(3)),
+                (tx_a, 0, 8, address(CREATED), address(WALLET), U256::from(3)),
+                (tx_b, 1, 1, address(ROUTER), address(LIBRARY), U256::from(9)),
+            ]
+        );
+    }
+}
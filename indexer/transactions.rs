@@ -1,8 +1,18 @@
 use std::fmt::Display;
 
-use rust_decimal::Decimal;
+use serde_json::{json, Value};
 use web3::types::U256;
 
+/// Who sent the transaction behind a transfer and what it cost, from its receipt.
+#[derive(Clone)]
+pub struct ReceiptDetails {
+    pub origin: String,
+    pub gas_used: Option<U256>,
+    pub effective_gas_price: Option<U256>,
+    pub status: Option<u64>,
+}
+
+#[allow(clippy::upper_case_acronyms)]
 pub enum TransferredToken {
     Fungible{address: String, amount: U256},
     NFT{address: String, token_id: U256},
@@ -13,12 +23,66 @@ pub struct Transaction {
     pub sender: String,
     pub recipient: String,
     pub tx_hash: String,
+    pub block_number: u64,
+    pub block_hash: String,
+    pub log_index: u64,
+    pub transaction_index: u64,
+    // set by parsers of events which name the operator, e.g. ERC1155
+    pub operator: Option<String>,
+    // filled by enrichment after parsing
+    pub block_timestamp: Option<u64>,
+    pub receipt: Option<ReceiptDetails>,
     pub transferred_token: TransferredToken,
 }
 
+impl Transaction {
+    /// Flat JSON view, amounts and ids as decimal strings since they overflow JSON numbers.
+    pub fn to_json(&self) -> Value {
+        let token = match &self.transferred_token {
+            TransferredToken::Fungible { address, amount } => json!({
+                "kind": "fungible",
+                "address": address,
+                "amount": amount.to_string(),
+            }),
+            TransferredToken::NFT { address, token_id } => json!({
+                "kind": "nft",
+                "address": address,
+                "token_id": token_id.to_string(),
+            }),
+            TransferredToken::ERC1155 { address, token_ids, amounts } => json!({
+                "kind": "erc1155",
+                "address": address,
+                "token_ids": token_ids.iter().map(|token_id| token_id.to_string()).collect::<Vec<_>>(),
+                "amounts": amounts.iter().map(|amount| amount.to_string()).collect::<Vec<_>>(),
+            }),
+        };
+        let receipt = self.receipt.as_ref().map(|receipt| {
+            json!({
+                "origin": receipt.origin,
+                "gas_used": receipt.gas_used.map(|gas_used| gas_used.to_string()),
+                "effective_gas_price": receipt.effective_gas_price.map(|gas_price| gas_price.to_string()),
+                "status": receipt.status,
+            })
+        });
+        json!({
+            "sender": self.sender,
+            "recipient": self.recipient,
+            "tx_hash": self.tx_hash,
+            "block_number": self.block_number,
+            "block_hash": self.block_hash,
+            "log_index": self.log_index,
+            "transaction_index": self.transaction_index,
+            "operator": self.operator,
+            "block_timestamp": self.block_timestamp,
+            "receipt": receipt,
+            "transferred_token": token,
+        })
+    }
+}
+
 impl Display for Transaction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}: {} -> {} \n", self.tx_hash, self.sender, self.recipient)?;
+        writeln!(f, "{} at block {} log {}: {} -> {} ", self.tx_hash, self.block_number, self.log_index, self.sender, self.recipient)?;
         match &self.transferred_token {
             TransferredToken::Fungible{address, amount} => {
                 write!(f, "                                                          fungible {address} amount {amount}")
@@ -0,0 +1,671 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex as SyncMutex,
+    },
+    time::{Duration, Instant},
+};
+
+use futures::{
+    future::{join_all, BoxFuture},
+    Future,
+};
+use log::{info, warn};
+use tokio::sync::Mutex;
+use jsonrpc_core::Call;
+use web3::{
+    error::TransportError,
+    helpers::build_request,
+    transports::{Either, WebSocket},
+    BatchTransport, RequestId, Transport,
+};
+
+use crate::models::Network;
+
+use super::{
+    commons::IndexerError,
+    http_transport::{HttpTimeouts, HttpTransport},
+    metrics,
+    rate_limiter::RateLimiter,
+    rpc_errors::{is_endpoint_failure, is_rate_limited},
+};
+
+// smoothing factor of latency and error rate averages
+const HEALTH_SMOOTHING: f64 = 0.2;
+const BASE_COOLDOWN_SECONDS: u64 = 5;
+const MAX_COOLDOWN_SECONDS: u64 = 300;
+// endpoint this many blocks behind the best one is demoted until it catches up
+const MAX_HEAD_LAG: u64 = 20;
+const HEAD_CHECK_INTERVAL_SECONDS: u64 = 60;
+// throttled call is retried on the same endpoint once the limiter allows it
+const MAX_RATE_LIMITED_RETRIES: u32 = 5;
+const RATE_LIMITED_PAUSE_SECONDS: u64 = 1;
+
+/// WebSocket for `ws://` and `wss://` rpc urls, HTTP otherwise.
+pub type EndpointTransport = Either<WebSocket, HttpTransport>;
+
+pub fn is_websocket_url(rpc_url: &str) -> bool {
+    rpc_url.starts_with("ws://") || rpc_url.starts_with("wss://")
+}
+
+#[derive(Debug, Default)]
+struct EndpointHealth {
+    latency_ms: f64,
+    error_rate: f64,
+    consecutive_failures: u32,
+    cooldown_until: Option<Instant>,
+    lagging: bool,
+}
+
+impl EndpointHealth {
+    fn is_available(&self, now: Instant) -> bool {
+        !self.lagging && self.cooldown_until.is_none_or(|until| until <= now)
+    }
+
+    /// Lower is better, used only when every endpoint is unavailable.
+    fn score(&self) -> f64 {
+        self.error_rate * 10_000.0 + self.latency_ms
+    }
+
+    fn record_success(&mut self, latency: Duration) {
+        let latency_ms = latency.as_secs_f64() * 1000.0;
+        self.latency_ms += HEALTH_SMOOTHING * (latency_ms - self.latency_ms);
+        self.error_rate -= HEALTH_SMOOTHING * self.error_rate;
+        self.consecutive_failures = 0;
+        self.cooldown_until = None;
+    }
+
+    fn record_failure(&mut self) {
+        self.error_rate += HEALTH_SMOOTHING * (1.0 - self.error_rate);
+        self.consecutive_failures += 1;
+        let cooldown = BASE_COOLDOWN_SECONDS
+            .saturating_mul(1 << std::cmp::min(self.consecutive_failures - 1, 16))
+            .min(MAX_COOLDOWN_SECONDS);
+        self.cooldown_until = Some(Instant::now() + Duration::from_secs(cooldown));
+    }
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    url: String,
+    // None while a WebSocket is not connected, it is connected again before its next call
+    transport: SyncMutex<Option<EndpointTransport>>,
+    health: SyncMutex<EndpointHealth>,
+}
+
+impl Endpoint {
+    fn transport(&self) -> Option<EndpointTransport> {
+        self.transport.lock().unwrap().clone()
+    }
+
+    /// Transport to call, reconnecting a failed WebSocket so health of the endpoint is kept.
+    async fn connected_transport(&self) -> web3::error::Result<EndpointTransport> {
+        if let Some(transport) = self.transport() {
+            return Ok(transport);
+        }
+        match WebSocket::new(&self.url).await {
+            Ok(websocket) => {
+                info!("Reconnected WebSocket {}", self.url);
+                let transport = Either::Left(websocket);
+                *self.transport.lock().unwrap() = Some(transport.clone());
+                Ok(transport)
+            }
+            Err(e) => Err(web3::Error::Transport(TransportError::Message(format!(
+                "During reconnecting to WebSocket {} occurred {e}",
+                self.url
+            )))),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Endpoints {
+    endpoints: Vec<Endpoint>,
+    limiter: Arc<RateLimiter>,
+    next_id: AtomicUsize,
+    last_head_check: SyncMutex<Option<Instant>>,
+}
+
+/// Ordered RPC endpoints of a network. Calls go to the first available endpoint
+/// and fail over to the next ones when an endpoint does not answer.
+#[derive(Debug, Clone)]
+pub struct RpcTransport {
+    inner: Arc<Endpoints>,
+}
+
+impl RpcTransport {
+    /// Endpoint indexes in the order calls should try them.
+    fn candidates(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let endpoints = &self.inner.endpoints;
+        let mut available = vec![];
+        let mut unavailable = vec![];
+        for (i, endpoint) in endpoints.iter().enumerate() {
+            let health = endpoint.health.lock().unwrap();
+            if health.is_available(now) {
+                available.push(i);
+            } else {
+                unavailable.push((i, health.score()));
+            }
+        }
+        // demoted endpoints are still tried last, best scored first
+        unavailable.sort_by(|a, b| a.1.total_cmp(&b.1));
+        available.extend(unavailable.into_iter().map(|(i, _)| i));
+        available
+    }
+
+    /// WebSocket of the preferred endpoint, if it has one.
+    pub fn websocket(&self) -> Option<WebSocket> {
+        let preferred = *self.candidates().first()?;
+        match self.inner.endpoints[preferred].transport() {
+            Some(Either::Left(websocket)) => Some(websocket),
+            _ => None,
+        }
+    }
+
+    /// Compares `eth_blockNumber` of every endpoint and demotes the ones lagging behind.
+    /// Runs at most once per HEAD_CHECK_INTERVAL_SECONDS.
+    pub async fn cross_check_heads(&self) {
+        if self.inner.endpoints.len() < 2 {
+            return;
+        }
+        {
+            let mut last_head_check = self.inner.last_head_check.lock().unwrap();
+            if last_head_check.is_some_and(|checked| checked.elapsed() < Duration::from_secs(HEAD_CHECK_INTERVAL_SECONDS)) {
+                return;
+            }
+            *last_head_check = Some(Instant::now());
+        }
+        let methods = [String::from("eth_blockNumber")];
+        let block_number = |transport: &EndpointTransport| web3::Web3::new(transport.clone()).eth().block_number();
+        let heads = join_all(self.inner.endpoints.iter().map(|endpoint| async {
+            match self.call_endpoint(endpoint, &methods, &block_number).await {
+                Ok(Ok(head)) => Some(head.as_u64()),
+                _ => None,
+            }
+        }))
+        .await;
+        let Some(best_head) = heads.iter().flatten().max().copied() else {
+            return;
+        };
+        for (endpoint, head) in self.inner.endpoints.iter().zip(heads) {
+            let mut health = endpoint.health.lock().unwrap();
+            let lagging = head.map_or(health.lagging, |head| head + MAX_HEAD_LAG < best_head);
+            if lagging != health.lagging {
+                if lagging {
+                    warn!("Endpoint {} is at {head:?} while best head is {best_head}, demoting it", endpoint.url);
+                } else {
+                    info!("Endpoint {} caught up to {best_head}", endpoint.url);
+                }
+            }
+            health.lagging = lagging;
+        }
+    }
+
+    pub fn health_report(&self) -> String {
+        let reports: Vec<String> = self
+            .inner
+            .endpoints
+            .iter()
+            .map(|endpoint| {
+                let health = endpoint.health.lock().unwrap();
+                format!(
+                    "{} latency {:.0}ms errors {:.0}%{}",
+                    endpoint.url,
+                    health.latency_ms,
+                    health.error_rate * 100.0,
+                    if health.is_available(Instant::now()) { "" } else { " demoted" }
+                )
+            })
+            .collect();
+        reports.join(", ")
+    }
+}
+
+impl Transport for RpcTransport {
+    type Out = BoxFuture<'static, web3::error::Result<jsonrpc_core::Value>>;
+
+    fn prepare(&self, method: &str, params: Vec<jsonrpc_core::Value>) -> (RequestId, jsonrpc_core::Call) {
+        let id = self.inner.next_id.fetch_add(1, Ordering::AcqRel);
+        (id, build_request(id, method, params))
+    }
+
+    fn send(&self, id: RequestId, request: jsonrpc_core::Call) -> Self::Out {
+        let transport = self.clone();
+        Box::pin(async move {
+            let methods = vec![get_method(&request)];
+            transport
+                .call_with_failover(&methods, |endpoint| endpoint.send(id, request.clone()))
+                .await
+        })
+    }
+}
+
+impl BatchTransport for RpcTransport {
+    type Batch = BoxFuture<'static, web3::error::Result<Vec<web3::error::Result<jsonrpc_core::Value>>>>;
+
+    fn send_batch<T>(&self, requests: T) -> Self::Batch
+    where
+        T: IntoIterator<Item = (RequestId, jsonrpc_core::Call)>,
+    {
+        let transport = self.clone();
+        let requests: Vec<(RequestId, jsonrpc_core::Call)> = requests.into_iter().collect();
+        Box::pin(async move {
+            let methods: Vec<String> = requests.iter().map(|(_, request)| get_method(request)).collect();
+            transport
+                .call_with_failover(&methods, |endpoint| endpoint.send_batch(requests.clone()))
+                .await
+        })
+    }
+}
+
+impl RpcTransport {
+    /// Calls endpoints in order of preference, the next endpoint is tried when one does not answer.
+    async fn call_with_failover<T, F, Fut>(&self, methods: &[String], call: F) -> web3::error::Result<T>
+    where
+        F: Fn(&EndpointTransport) -> Fut,
+        Fut: Future<Output = web3::error::Result<T>>,
+    {
+        let candidates = self.candidates();
+        let mut last_error = web3::Error::Unreachable;
+        for (attempt, i) in candidates.iter().enumerate() {
+            let endpoint = &self.inner.endpoints[*i];
+            match self.call_endpoint(endpoint, methods, &call).await {
+                Ok(result) => return result,
+                Err(e) => {
+                    if attempt + 1 < candidates.len() {
+                        warn!("Endpoint {} failed with {e}, failing over", endpoint.url);
+                    }
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Calls one endpoint, reconnecting it if needed, within the rate limit and with metrics.
+    /// Throttled calls are retried once the limiter allows. Returns Err if the endpoint
+    /// did not answer, its health is updated either way.
+    async fn call_endpoint<T, F, Fut>(
+        &self,
+        endpoint: &Endpoint,
+        methods: &[String],
+        call: &F,
+    ) -> Result<web3::error::Result<T>, web3::Error>
+    where
+        F: Fn(&EndpointTransport) -> Fut,
+        Fut: Future<Output = web3::error::Result<T>>,
+    {
+        let limiter = &self.inner.limiter;
+        let transport = match endpoint.connected_transport().await {
+            Ok(transport) => transport,
+            Err(e) => {
+                endpoint.health.lock().unwrap().record_failure();
+                return Err(e);
+            }
+        };
+        let mut rate_limited_retries = 0;
+        loop {
+            for method in methods.iter() {
+                limiter.acquire(method).await;
+            }
+            let started = Instant::now();
+            let result = call(&transport).await;
+            record_call_metrics(limiter.network_name(), methods, started.elapsed(), result.is_err());
+            match result {
+                Err(e) if is_rate_limited(&e) && rate_limited_retries < MAX_RATE_LIMITED_RETRIES => {
+                    // a longer Retry-After pause set by HttpTransport is kept
+                    limiter.pause_for(Duration::from_secs(RATE_LIMITED_PAUSE_SECONDS));
+                    rate_limited_retries += 1;
+                }
+                Err(e) if is_endpoint_failure(&e) => {
+                    endpoint.health.lock().unwrap().record_failure();
+                    if matches!(transport, Either::Left(_)) {
+                        *endpoint.transport.lock().unwrap() = None;
+                    }
+                    return Err(e);
+                }
+                // node answered, even with an RPC error, so endpoint is healthy
+                result => {
+                    endpoint.health.lock().unwrap().record_success(started.elapsed());
+                    return Ok(result);
+                }
+            }
+        }
+    }
+}
+
+fn record_call_metrics(network_name: &str, methods: &[String], duration: Duration, failed: bool) {
+    let method = method_label(methods);
+    let labels = [("network", network_name), ("method", method.as_str())];
+    metrics::observe("evm_indexer_rpc_duration_seconds", &labels, duration);
+    if failed {
+        metrics::inc_counter("evm_indexer_rpc_errors_total", &labels, 1);
+    }
+}
+
+/// Batches are labelled apart from single calls, as their latency is one of the whole batch.
+fn method_label(methods: &[String]) -> String {
+    match methods {
+        [method] => method.clone(),
+        [first, rest @ ..] if rest.iter().all(|method| method == first) => format!("batch:{first}"),
+        _ => String::from("batch"),
+    }
+}
+
+fn get_method(request: &Call) -> String {
+    match request {
+        Call::MethodCall(call) => call.method.clone(),
+        Call::Notification(notification) => notification.method.clone(),
+        Call::Invalid { .. } => String::new(),
+    }
+}
+
+// rpc urls and timeouts a transport was built with
+type TransportSettings = (Vec<String>, HttpTimeouts);
+
+/// Keeps one transport per network so indexers on the same network share connections.
+pub struct TransportPool {
+    transports: Mutex<HashMap<i64, (TransportSettings, RpcTransport)>>,
+    // kept apart from transports so rebuilding a transport does not reset the budget
+    limiters: Mutex<HashMap<i64, Arc<RateLimiter>>>,
+}
+
+impl TransportPool {
+    pub fn new() -> Self {
+        Self {
+            transports: Mutex::new(HashMap::new()),
+            limiters: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn get(&self, network: &Network) -> Result<RpcTransport, IndexerError> {
+        let limiter = self.get_limiter(network).await;
+        let timeouts = HttpTimeouts {
+            request: Duration::from_secs(network.rpc_timeout_seconds),
+            connect: Duration::from_secs(network.rpc_connect_timeout_seconds),
+        };
+        let settings = (network.get_rpc_urls(), timeouts);
+        let mut transports = self.transports.lock().await;
+        if let Some((cached_settings, transport)) = transports.get(&network.id) {
+            if *cached_settings == settings {
+                return Ok(transport.clone());
+            }
+        }
+        // network is new or its rpc urls or timeouts were changed
+        let transport = build_transport(&settings.0, limiter, timeouts).await?;
+        transports.insert(network.id, (settings, transport.clone()));
+        Ok(transport)
+    }
+
+    async fn get_limiter(&self, network: &Network) -> Arc<RateLimiter> {
+        let mut limiters = self.limiters.lock().await;
+        match limiters.get(&network.id) {
+            Some(limiter) => {
+                limiter.update_limits(network.rpc_requests_per_second, network.rpc_compute_units_per_second);
+                limiter.clone()
+            }
+            None => {
+                let limiter = Arc::new(RateLimiter::new(
+                    network.name.as_str(),
+                    network.rpc_requests_per_second,
+                    network.rpc_compute_units_per_second,
+                ));
+                limiters.insert(network.id, limiter.clone());
+                limiter
+            }
+        }
+    }
+}
+
+async fn build_transport(
+    rpc_urls: &[String],
+    limiter: Arc<RateLimiter>,
+    timeouts: HttpTimeouts,
+) -> Result<RpcTransport, IndexerError> {
+    let mut endpoints = vec![];
+    let mut errors = vec![];
+    for rpc_url in rpc_urls.iter() {
+        // one unreachable endpoint must not stop the others from being used
+        match build_endpoint_transport(rpc_url, limiter.clone(), timeouts).await {
+            Ok(transport) => endpoints.push(Endpoint {
+                url: rpc_url.clone(),
+                transport: SyncMutex::new(Some(transport)),
+                health: SyncMutex::new(EndpointHealth::default()),
+            }),
+            // WebSocket down during startup is kept as unhealthy and reconnected later
+            Err(e) if is_websocket_url(rpc_url) => {
+                warn!("Endpoint is not connected: {}", e.reason);
+                let mut health = EndpointHealth::default();
+                health.record_failure();
+                endpoints.push(Endpoint {
+                    url: rpc_url.clone(),
+                    transport: SyncMutex::new(None),
+                    health: SyncMutex::new(health),
+                });
+                errors.push(e.reason);
+            }
+            Err(e) => {
+                warn!("Skipping endpoint: {}", e.reason);
+                errors.push(e.reason);
+            }
+        }
+    }
+    if endpoints.iter().all(|endpoint| endpoint.transport().is_none()) {
+        return Err(IndexerError {
+            reason: format!("No RPC endpoint is usable: {}", errors.join("; ")),
+        });
+    }
+    Ok(RpcTransport {
+        inner: Arc::new(Endpoints {
+            endpoints,
+            limiter,
+            next_id: AtomicUsize::new(1),
+            last_head_check: SyncMutex::new(None),
+        }),
+    })
+}
+
+async fn build_endpoint_transport(
+    rpc_url: &str,
+    limiter: Arc<RateLimiter>,
+    timeouts: HttpTimeouts,
+) -> Result<EndpointTransport, IndexerError> {
+    if is_websocket_url(rpc_url) {
+        match WebSocket::new(rpc_url).await {
+            Ok(transport) => Ok(Either::Left(transport)),
+            Err(e) => Err(IndexerError {
+                reason: format!("During connecting to WebSocket {rpc_url} occurred {e}"),
+            }),
+        }
+    } else {
+        match HttpTransport::new(rpc_url, limiter, timeouts) {
+            Ok(transport) => Ok(Either::Right(transport)),
+            Err(e) => Err(IndexerError {
+                reason: format!("Bad RPC url {rpc_url}: {e}"),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        net::SocketAddr,
+        sync::{atomic::AtomicUsize, Arc, Mutex as SyncMutex},
+        time::{Duration, Instant},
+    };
+
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Response, Server,
+    };
+
+    use super::{
+        build_transport, method_label, Endpoint, EndpointHealth, Endpoints, RpcTransport, BASE_COOLDOWN_SECONDS,
+        MAX_COOLDOWN_SECONDS,
+    };
+    use crate::indexer::{http_transport::HttpTimeouts, rate_limiter::RateLimiter};
+
+    const TIMEOUTS: HttpTimeouts = HttpTimeouts {
+        request: Duration::from_millis(500),
+        connect: Duration::from_millis(500),
+    };
+
+    // nothing listens on the port, so connections are refused right away
+    const REFUSED_HTTP_URL: &str = "http://127.0.0.1:1";
+    const REFUSED_WS_URL: &str = "ws://127.0.0.1:1";
+
+    /// Node answering every call with block number 0x10.
+    fn serve_node() -> String {
+        let address = SocketAddr::from(([127, 0, 0, 1], 0));
+        let server = Server::bind(&address).serve(make_service_fn(|_| async {
+            Ok::<_, Infallible>(service_fn(|_| async {
+                Ok::<_, Infallible>(Response::new(Body::from(r#"{"jsonrpc":"2.0","id":0,"result":"0x10"}"#)))
+            }))
+        }));
+        let url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+        url
+    }
+
+    fn endpoint(url: &str, health: EndpointHealth) -> Endpoint {
+        Endpoint {
+            url: String::from(url),
+            transport: SyncMutex::new(None),
+            health: SyncMutex::new(health),
+        }
+    }
+
+    fn rpc_transport(endpoints: Vec<Endpoint>) -> RpcTransport {
+        RpcTransport {
+            inner: Arc::new(Endpoints {
+                endpoints,
+                limiter: Arc::new(RateLimiter::new("test", None, None)),
+                next_id: AtomicUsize::new(1),
+                last_head_check: SyncMutex::new(None),
+            }),
+        }
+    }
+
+    #[test]
+    fn backs_off_failed_endpoint_until_it_answers() {
+        let mut health = EndpointHealth::default();
+        health.record_failure();
+        let cooldown = health.cooldown_until.unwrap() - Instant::now();
+        assert!(cooldown <= Duration::from_secs(BASE_COOLDOWN_SECONDS));
+        assert!(!health.is_available(Instant::now()));
+        health.record_failure();
+        assert!(health.cooldown_until.unwrap() - Instant::now() > Duration::from_secs(BASE_COOLDOWN_SECONDS));
+        for _ in 0..20 {
+            health.record_failure();
+        }
+        assert!(health.cooldown_until.unwrap() - Instant::now() <= Duration::from_secs(MAX_COOLDOWN_SECONDS));
+        assert!(health.error_rate > 0.9);
+
+        health.record_success(Duration::from_millis(100));
+        assert!(health.is_available(Instant::now()));
+        assert_eq!(health.consecutive_failures, 0);
+        assert!(health.error_rate < 0.9);
+    }
+
+    #[test]
+    fn orders_available_endpoints_first_and_the_rest_by_score() {
+        let mut cooling_down = EndpointHealth::default();
+        cooling_down.record_failure();
+        let lagging = EndpointHealth {
+            lagging: true,
+            latency_ms: 50.0,
+            ..Default::default()
+        };
+        let mut failing = EndpointHealth::default();
+        failing.record_failure();
+        failing.record_failure();
+        let transport = rpc_transport(vec![
+            endpoint("http://a", cooling_down),
+            endpoint("http://b", lagging),
+            endpoint("http://c", EndpointHealth::default()),
+            endpoint("http://d", failing),
+            endpoint("http://e", EndpointHealth::default()),
+        ]);
+        assert_eq!(transport.candidates(), vec![2, 4, 1, 0, 3]);
+    }
+
+    #[tokio::test]
+    async fn fails_over_to_next_endpoint() {
+        let node_url = serve_node();
+        let transport = build_transport(
+            &[String::from(REFUSED_HTTP_URL), node_url],
+            Arc::new(RateLimiter::new("test", None, None)),
+            TIMEOUTS,
+        )
+        .await
+        .unwrap();
+
+        let head = web3::Web3::new(transport.clone()).eth().block_number().await.unwrap();
+
+        assert_eq!(head.as_u64(), 0x10);
+        let endpoints = &transport.inner.endpoints;
+        assert_eq!(endpoints[0].health.lock().unwrap().consecutive_failures, 1);
+        assert!(endpoints[1].health.lock().unwrap().is_available(Instant::now()));
+        assert_eq!(transport.candidates(), vec![1, 0]);
+    }
+
+    #[tokio::test]
+    async fn fails_over_from_endpoint_which_never_answers() {
+        // accepts connections but never responds to them
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let hung_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut connections = vec![];
+            while let Ok((connection, _)) = listener.accept().await {
+                connections.push(connection);
+            }
+        });
+        let transport = build_transport(
+            &[hung_url, serve_node()],
+            Arc::new(RateLimiter::new("test", None, None)),
+            TIMEOUTS,
+        )
+        .await
+        .unwrap();
+
+        let head = web3::Web3::new(transport.clone()).eth().block_number().await.unwrap();
+
+        assert_eq!(head.as_u64(), 0x10);
+        assert_eq!(transport.inner.endpoints[0].health.lock().unwrap().consecutive_failures, 1);
+    }
+
+    #[tokio::test]
+    async fn keeps_websocket_down_at_startup_as_unhealthy() {
+        let node_url = serve_node();
+        let transport = build_transport(
+            &[String::from(REFUSED_WS_URL), node_url],
+            Arc::new(RateLimiter::new("test", None, None)),
+            TIMEOUTS,
+        )
+        .await
+        .unwrap();
+
+        let endpoints = &transport.inner.endpoints;
+        assert_eq!(endpoints.len(), 2);
+        assert!(endpoints[0].transport().is_none());
+        assert_eq!(transport.candidates(), vec![1, 0]);
+    }
+
+    fn methods(methods: &[&str]) -> Vec<String> {
+        methods.iter().map(|method| method.to_string()).collect()
+    }
+
+    #[test]
+    fn labels_batches_with_their_method() {
+        assert_eq!(method_label(&methods(&["eth_getLogs"])), "eth_getLogs");
+        assert_eq!(
+            method_label(&methods(&["eth_getTransactionReceipt", "eth_getTransactionReceipt"])),
+            "batch:eth_getTransactionReceipt"
+        );
+        assert_eq!(method_label(&methods(&["eth_getBlockByNumber", "trace_block"])), "batch");
+    }
+}
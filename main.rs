@@ -1,14 +1,21 @@
-extern crate postgres;
+extern crate tokio_postgres;
 extern crate serde_json;
 extern crate web3;
 extern crate log;
 extern crate rust_decimal;
 
-use std::env;
-use postgres::{Client, NoTls};
+use std::{env, process::ExitCode};
+use tokio::sync::watch;
+use tokio_postgres::{Client, NoTls};
+mod cli;
 mod indexer;
 mod models;
-use indexer::start;
+use cli::{parse_args, Cli, Command, USAGE};
+use models::load_file_config;
+use indexer::{
+    backfill, dry_run, list, listen_for_signals, reindex, serve_metrics, start, status, supervise, BackfillOptions,
+    ExitStatus,
+};
 
 pub struct DBClientError {
     pub reason: String,
@@ -20,20 +27,26 @@ pub fn get_env(key: &str) -> Result<String, String> {
             Ok(env_value)
         },
         Err(e) => {
-            Err(format!("On fetching env {} occurred error: {}", key, e.to_string()))
+            Err(format!("On fetching env {} occurred error: {}", key, e))
         }
     }
 }
 
-pub fn init_db_client() -> Result<Client, String> {
+pub async fn init_db_client() -> Result<Client, String> {
     let postgres_db = get_env("POSTGRES_DB")?;
     let postgres_user = get_env("POSTGRES_USER")?;
     let postgres_password = get_env("POSTGRES_PASSWORD")?;
     let postgres_host = get_env("POSTGRES_HOST")?;
     let postgres_port = get_env("POSTGRES_PORT").unwrap_or(String::from("5432"));
     let connection_string = format!("postgresql://{}:{}@{}:{}/{}", postgres_user, postgres_password, postgres_host, postgres_port, postgres_db);
-    match Client::connect(connection_string.as_str(), NoTls) {
-        Ok(client) => {
+    match tokio_postgres::connect(connection_string.as_str(), NoTls).await {
+        Ok((client, connection)) => {
+            // connection drives the socket and resolves when client is dropped or connection is lost
+            tokio::spawn(async move {
+                if let Err(e) = connection.await {
+                    log::warn!("Database connection closed with {e}");
+                }
+            });
             log::info!("DB Client initialized with connection url {}", connection_string);
             Ok(client)
         },
@@ -43,14 +56,66 @@ pub fn init_db_client() -> Result<Client, String> {
     }
 }
 
-pub fn main() {
+#[tokio::main]
+pub async fn main() -> ExitCode {
     env_logger::init();
-    match init_db_client() {
-        Ok(mut client) => {
-            start(&mut client);
-        },
+    let args: Vec<String> = env::args().skip(1).collect();
+    let command = match parse_args(&args) {
+        Ok(Cli { command: Command::Help, .. }) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Ok(Cli { command, config_path }) => {
+            if let Some(config_path) = config_path {
+                if let Err(e) = load_file_config(&config_path) {
+                    log::warn!("{}", e.reason);
+                    return ExitCode::from(ExitStatus::Failed.code());
+                }
+                log::info!("Networks, tokens and indexers are taken from {config_path}");
+            }
+            command
+        }
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            return ExitCode::from(ExitStatus::BadUsage.code());
+        }
+    };
+    let shutdown = listen_for_signals();
+    if let Ok(metrics_address) = get_env("METRICS_ADDRESS") {
+        if !metrics_address.is_empty() {
+            serve_metrics(&metrics_address);
+        }
+    }
+    let exit_status = match init_db_client().await {
+        Ok(mut client) => run_command(&mut client, command, shutdown).await,
         Err(e) => {
             log::warn!("{e}");
+            ExitStatus::Failed
+        }
+    };
+    ExitCode::from(exit_status.code())
+}
+
+async fn run_command(client: &mut Client, command: Command, shutdown: watch::Receiver<bool>) -> ExitStatus {
+    match command {
+        Command::Run { indexer_name } => start(client, &indexer_name, shutdown).await,
+        Command::RunAll => supervise(client, shutdown).await,
+        Command::Backfill { indexer_name, from_block, to_block } => {
+            let options = BackfillOptions {
+                from_block,
+                to_block,
+                ..BackfillOptions::from_env()
+            };
+            backfill(client, &indexer_name, options, shutdown).await
+        }
+        Command::Reindex { indexer_name, from_block, to_block } => {
+            reindex(client, &indexer_name, from_block, to_block, shutdown).await
+        }
+        Command::DryRun { indexer_name, from_block, to_block } => {
+            dry_run(client, &indexer_name, from_block, to_block, shutdown).await
         }
+        Command::List => list(client).await,
+        Command::Status => status(client).await,
+        Command::Help => ExitStatus::Completed,
     }
 }
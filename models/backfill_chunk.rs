@@ -0,0 +1,73 @@
+use tokio_postgres::{Client, Row};
+
+use super::{Indexer, ModelError};
+
+const TABLE_NAME: &str = "indexer_backfill_chunk";
+
+/// Block range completed by a backfill worker, kept until indexer last block covers it.
+pub struct BackfillChunk {
+    pub indexer_id: i64,
+    pub from_block: u64,
+    pub to_block: u64,
+}
+
+impl BackfillChunk {
+    pub async fn load_from_db(client: &mut Client, indexer: &Indexer) -> Result<Vec<Self>, ModelError> {
+        let query = format!("SELECT indexer_id, from_block, to_block FROM {TABLE_NAME} WHERE indexer_id = $1 ORDER BY from_block");
+        match client.query(query.as_str(), &[&indexer.id]).await {
+            Ok(rows) => Ok(rows.iter().map(Self::from_row).collect()),
+            Err(e) => Err(ModelError {
+                reason: e.to_string(),
+            }),
+        }
+    }
+
+    pub async fn add_to_db_tx(&self, db_tx: &mut tokio_postgres::Transaction<'_>) -> Result<(), ModelError> {
+        let query = format!("INSERT INTO {TABLE_NAME} (indexer_id, from_block, to_block) VALUES ($1, $2, $3) ON CONFLICT (indexer_id, from_block) DO UPDATE SET to_block = GREATEST({TABLE_NAME}.to_block, EXCLUDED.to_block)");
+        match db_tx.execute(
+            query.as_str(),
+            &[&self.indexer_id, &(self.from_block as i64), &(self.to_block as i64)],
+        ).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(ModelError {
+                reason: format!(
+                    "During saving backfill chunk {} to {} occurred {e}",
+                    self.from_block, self.to_block
+                ),
+            }),
+        }
+    }
+
+    /// Removes chunks which indexer last block has already moved over.
+    pub async fn delete_covered(client: &mut Client, indexer: &Indexer) -> Result<(), ModelError> {
+        let query = format!("DELETE FROM {TABLE_NAME} WHERE indexer_id = $1 AND to_block <= $2");
+        match client.execute(query.as_str(), &[&indexer.id, &(indexer.last_block as i64)]).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(ModelError {
+                reason: format!("During deleting covered backfill chunks occurred {e}"),
+            }),
+        }
+    }
+
+    pub async fn delete_after_block(
+        db_tx: &mut tokio_postgres::Transaction<'_>,
+        indexer: &Indexer,
+        block_number: u64,
+    ) -> Result<(), ModelError> {
+        let query = format!("DELETE FROM {TABLE_NAME} WHERE indexer_id = $1 AND to_block > $2");
+        match db_tx.execute(query.as_str(), &[&indexer.id, &(block_number as i64)]).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(ModelError {
+                reason: format!("During deleting backfill chunks after block {block_number} occurred {e}"),
+            }),
+        }
+    }
+
+    pub fn from_row(row: &Row) -> Self {
+        Self {
+            indexer_id: row.get(0),
+            from_block: row.get::<usize, i64>(1) as u64,
+            to_block: row.get::<usize, i64>(2) as u64,
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use tokio_postgres::{Client, Row};
+
+use super::{Indexer, ModelError};
+
+const TABLE_NAME: &str = "indexer_block_hash";
+
+pub struct BlockHash {
+    pub indexer_id: i64,
+    pub block_number: u64,
+    pub block_hash: String,
+}
+
+impl BlockHash {
+    pub async fn load_latest_from_db(
+        client: &mut Client,
+        indexer: &Indexer,
+        limit: u64,
+    ) -> Result<Vec<Self>, ModelError> {
+        let query = format!("SELECT indexer_id, block_number, block_hash FROM {TABLE_NAME} WHERE indexer_id = $1 AND block_number <= $2 ORDER BY block_number DESC LIMIT $3");
+        match client.query(
+            query.as_str(),
+            &[&indexer.id, &(indexer.last_block as i64), &(limit as i64)],
+        ).await {
+            Ok(rows) => Ok(rows.iter().map(Self::from_row).collect()),
+            Err(e) => Err(ModelError {
+                reason: e.to_string(),
+            }),
+        }
+    }
+
+    pub async fn add_to_db_tx(&self, db_tx: &mut tokio_postgres::Transaction<'_>) -> Result<(), ModelError> {
+        let query = format!("INSERT INTO {TABLE_NAME} (indexer_id, block_number, block_hash) VALUES ($1, $2, $3) ON CONFLICT (indexer_id, block_number) DO UPDATE SET block_hash = EXCLUDED.block_hash");
+        match db_tx.execute(
+            query.as_str(),
+            &[&self.indexer_id, &(self.block_number as i64), &self.block_hash],
+        ).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(ModelError {
+                reason: format!(
+                    "During saving hash of block {} occurred {e}",
+                    self.block_number
+                ),
+            }),
+        }
+    }
+
+    pub async fn delete_after_block(
+        db_tx: &mut tokio_postgres::Transaction<'_>,
+        indexer: &Indexer,
+        block_number: u64,
+    ) -> Result<(), ModelError> {
+        let query = format!("DELETE FROM {TABLE_NAME} WHERE indexer_id = $1 AND block_number > $2");
+        match db_tx.execute(query.as_str(), &[&indexer.id, &(block_number as i64)]).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(ModelError {
+                reason: format!("During deleting block hashes after {block_number} occurred {e}"),
+            }),
+        }
+    }
+
+    /// Hashes deeper than the reorg window are never checked again.
+    pub async fn delete_before_block(
+        db_tx: &mut tokio_postgres::Transaction<'_>,
+        indexer: &Indexer,
+        block_number: u64,
+    ) -> Result<(), ModelError> {
+        let query = format!("DELETE FROM {TABLE_NAME} WHERE indexer_id = $1 AND block_number < $2");
+        match db_tx.execute(query.as_str(), &[&indexer.id, &(block_number as i64)]).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(ModelError {
+                reason: format!("During pruning block hashes before {block_number} occurred {e}"),
+            }),
+        }
+    }
+
+    pub fn from_row(row: &Row) -> Self {
+        Self {
+            indexer_id: row.get(0),
+            block_number: row.get::<usize, i64>(1) as u64,
+            block_hash: row.get(2),
+        }
+    }
+}
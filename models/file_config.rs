@@ -0,0 +1,385 @@
+use std::{collections::HashSet, fs, sync::OnceLock};
+
+use rust_decimal::Decimal;
+use toml::Value;
+
+use super::{
+    network::{positive_rate_limit, positive_timeout, DEFAULT_RPC_CONNECT_TIMEOUT_SECONDS, DEFAULT_RPC_TIMEOUT_SECONDS},
+    HeadBlockTag, Indexer, IndexerStatus, ModelError, Network, Token, TokenType, TraceApi};
+
+static FILE_CONFIG: OnceLock<FileConfig> = OnceLock::new();
+
+/// Networks, tokens and indexers defined in a TOML file instead of the Django-owned tables.
+/// Progress of indexers is kept in the indexer_state table, as the file is never written.
+pub struct FileConfig {
+    pub networks: Vec<Network>,
+    pub tokens: Vec<Token>,
+    pub indexers: Vec<IndexerDefinition>,
+}
+
+/// Indexer as defined in the file, its last block and status come from the state table.
+pub struct IndexerDefinition {
+    pub indexer: Indexer,
+    pub token_ids: Vec<i64>,
+}
+
+/// Configuration loaded by `load_file_config`, if the process runs without Django tables.
+pub fn file_config() -> Option<&'static FileConfig> {
+    FILE_CONFIG.get()
+}
+
+pub fn load_file_config(path: &str) -> Result<(), ModelError> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            return Err(ModelError {
+                reason: format!("During reading config {path} occurred {e}"),
+            })
+        }
+    };
+    let root = match content.parse::<Value>() {
+        Ok(root) => root,
+        Err(e) => {
+            return Err(ModelError {
+                reason: format!("Config {path} is not valid TOML: {e}"),
+            })
+        }
+    };
+    let config = parse_config(&root)?;
+    if FILE_CONFIG.set(config).is_err() {
+        return Err(ModelError {
+            reason: String::from("Config is already loaded"),
+        });
+    }
+    Ok(())
+}
+
+fn parse_config(root: &Value) -> Result<FileConfig, ModelError> {
+    let mut networks = vec![];
+    for table in get_tables(root, "networks")? {
+        let name = get_string(table, "name")?;
+        networks.push(Network {
+            id: get_int(table, "id")?,
+            chain_id: get_int(table, "chain_id")?,
+            name: name.clone(),
+            rpc_url: get_string(table, "rpc_url")?,
+            fallback_rpc_urls: match table.get("fallback_rpc_urls").and_then(|urls| urls.as_array()) {
+                Some(urls) => urls.iter().filter_map(|url| url.as_str().map(String::from)).collect(),
+                None => vec![],
+            },
+            max_step: get_unsigned(table, "max_step")?,
+            network_type: get_string_or(table, "network_type", "mainnet"),
+            need_poa: table.get("need_poa").and_then(|value| value.as_bool()).unwrap_or(false),
+            explorer_url: get_string_or(table, "explorer_url", ""),
+            confirmations: get_optional_unsigned(table, "confirmations")?.unwrap_or(0),
+            head_block_tag: parse_entry(
+                "Network",
+                &name,
+                "head_block_tag",
+                HeadBlockTag::try_from(&get_string_or(table, "head_block_tag", "latest")),
+            )?,
+            rpc_requests_per_second: positive_rate_limit(
+                &name,
+                "rpc_requests_per_second",
+                get_optional_float(table, "rpc_requests_per_second"),
+            ),
+            rpc_compute_units_per_second: positive_rate_limit(
+                &name,
+                "rpc_compute_units_per_second",
+                get_optional_float(table, "rpc_compute_units_per_second"),
+            ),
+            trace_api: match table.get("trace_api").and_then(|value| value.as_str()) {
+                Some(trace_api) => Some(parse_entry(
+                    "Network",
+                    &name,
+                    "trace_api",
+                    TraceApi::try_from(&trace_api.to_string()),
+                )?),
+                None => None,
+            },
+            rpc_timeout_seconds: positive_timeout(
+                &name,
+                "rpc_timeout_seconds",
+                get_optional_int(table, "rpc_timeout_seconds"),
+                DEFAULT_RPC_TIMEOUT_SECONDS,
+            ),
+            rpc_connect_timeout_seconds: positive_timeout(
+                &name,
+                "rpc_connect_timeout_seconds",
+                get_optional_int(table, "rpc_connect_timeout_seconds"),
+                DEFAULT_RPC_CONNECT_TIMEOUT_SECONDS,
+            ),
+        });
+    }
+    ensure_unique("network", "id", networks.iter().map(|network| network.id.to_string()))?;
+    ensure_unique("network", "name", networks.iter().map(|network| network.name.clone()))?;
+    let network_id = |name: &String| match networks.iter().find(|network| &network.name == name) {
+        Some(network) => Ok(network.id),
+        None => Err(ModelError {
+            reason: format!("Config refers to unknown network {name}"),
+        }),
+    };
+    let mut tokens = vec![];
+    for table in get_tables(root, "tokens")? {
+        let name = get_string(table, "name")?;
+        tokens.push(Token {
+            id: get_int(table, "id")?,
+            address: get_string(table, "address")?,
+            token_type: parse_entry("Token", &name, "type", TokenType::try_from(&get_string(table, "type")?))?,
+            name,
+            strategy: get_string_or(table, "strategy", "token_scan"),
+            total_supply: Decimal::ZERO,
+            volume: Decimal::ZERO,
+            network_id: network_id(&get_string(table, "network")?)?,
+        });
+    }
+    let network_name = |network_id: i64| match networks.iter().find(|network| network.id == network_id) {
+        Some(network) => network.name.clone(),
+        None => network_id.to_string(),
+    };
+    ensure_unique("token", "id", tokens.iter().map(|token| token.id.to_string()))?;
+    ensure_unique(
+        "token",
+        "name",
+        tokens.iter().map(|token| format!("{} on network {}", token.name, network_name(token.network_id))),
+    )?;
+    // logs are routed to tokens by address, native tokens have no logs so their address is not used
+    ensure_unique(
+        "token",
+        "address",
+        tokens
+            .iter()
+            .filter(|token| !token.token_type.is_native())
+            .map(|token| format!("{} on network {}", token.address.to_lowercase(), network_name(token.network_id))),
+    )?;
+    let mut indexers = vec![];
+    for table in get_tables(root, "indexers")? {
+        let name = get_string(table, "name")?;
+        let network_id = network_id(&get_string(table, "network")?)?;
+        let mut token_ids = vec![];
+        for token_name in table.get("tokens").and_then(|names| names.as_array()).into_iter().flatten() {
+            match tokens
+                .iter()
+                .find(|token| Some(token.name.as_str()) == token_name.as_str() && token.network_id == network_id)
+            {
+                Some(token) => token_ids.push(token.id),
+                None => {
+                    return Err(ModelError {
+                        reason: format!("Indexer {name} watches unknown token {token_name} of its network"),
+                    })
+                }
+            }
+        }
+        // strategy params are kept as JSON, like the Django JSONField they replace
+        let strategy_params = match table.get("strategy_params") {
+            Some(params) => match serde_json::to_value(params) {
+                Ok(params) => Some(params),
+                Err(e) => {
+                    return Err(ModelError {
+                        reason: format!("Strategy params of indexer {name} are invalid: {e}"),
+                    })
+                }
+            },
+            None => None,
+        };
+        indexers.push(IndexerDefinition {
+            indexer: Indexer {
+                id: get_int(table, "id")?,
+                name,
+                last_block: 0,
+                strategy: get_string_or(table, "strategy", "token_scan"),
+                short_sleep_seconds: get_optional_int(table, "short_sleep_seconds").unwrap_or(1),
+                long_sleep_seconds: get_optional_int(table, "long_sleep_seconds").unwrap_or(10),
+                strategy_params,
+                network_id,
+                status: IndexerStatus::Running.to_string(),
+                indexer_type: get_string_or(table, "type", "default"),
+                reindex_block: None,
+                start_block: get_optional_unsigned(table, "start_block")?,
+                end_block: get_optional_unsigned(table, "end_block")?,
+                enrich_transactions: table.get("enrich_transactions").and_then(|value| value.as_bool()).unwrap_or(false),
+                index_internal_transfers: table
+                    .get("index_internal_transfers")
+                    .and_then(|value| value.as_bool())
+                    .unwrap_or(false),
+            },
+            token_ids,
+        });
+    }
+    ensure_unique("indexer", "id", indexers.iter().map(|definition| definition.indexer.id.to_string()))?;
+    ensure_unique("indexer", "name", indexers.iter().map(|definition| definition.indexer.name.clone()))?;
+    Ok(FileConfig {
+        networks,
+        tokens,
+        indexers,
+    })
+}
+
+/// Ids and names identify entries in the database and in other entries, so one value must mean one entry.
+fn ensure_unique(kind: &str, key: &str, values: impl Iterator<Item = String>) -> Result<(), ModelError> {
+    let mut seen = HashSet::new();
+    for value in values {
+        if !seen.insert(value.clone()) {
+            return Err(ModelError {
+                reason: format!("Config has more than one {kind} with {key} {value}"),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn get_tables<'a>(root: &'a Value, key: &str) -> Result<Vec<&'a Value>, ModelError> {
+    match root.get(key) {
+        Some(Value::Array(tables)) => Ok(tables.iter().collect()),
+        Some(_) => Err(ModelError {
+            reason: format!("Config {key} should be an array of tables, [[{key}]]"),
+        }),
+        None => Ok(vec![]),
+    }
+}
+
+fn get_string(table: &Value, key: &str) -> Result<String, ModelError> {
+    match table.get(key).and_then(|value| value.as_str()) {
+        Some(value) => Ok(value.to_string()),
+        None => Err(ModelError {
+            reason: format!("Config entry {table} has no string {key}"),
+        }),
+    }
+}
+
+fn get_string_or(table: &Value, key: &str, default: &str) -> String {
+    get_string(table, key).unwrap_or(String::from(default))
+}
+
+fn get_int(table: &Value, key: &str) -> Result<i64, ModelError> {
+    match get_optional_int(table, key) {
+        Some(value) => Ok(value),
+        None => Err(ModelError {
+            reason: format!("Config entry {table} has no integer {key}"),
+        }),
+    }
+}
+
+fn get_optional_int(table: &Value, key: &str) -> Option<i64> {
+    table.get(key).and_then(|value| value.as_integer())
+}
+
+fn get_unsigned(table: &Value, key: &str) -> Result<u64, ModelError> {
+    match get_optional_unsigned(table, key)? {
+        Some(value) => Ok(value),
+        None => Err(ModelError {
+            reason: format!("Config entry {table} has no integer {key}"),
+        }),
+    }
+}
+
+/// Block numbers and counts, which would wrap around if a negative value were cast.
+fn get_optional_unsigned(table: &Value, key: &str) -> Result<Option<u64>, ModelError> {
+    match get_optional_int(table, key) {
+        Some(value) if value < 0 => Err(ModelError {
+            reason: format!("Config entry {table} has negative {key} {value}"),
+        }),
+        Some(value) => Ok(Some(value as u64)),
+        None => Ok(None),
+    }
+}
+
+/// Names the entry a value of the given field could not be parsed for.
+fn parse_entry<T>(kind: &str, name: &str, key: &str, parsed: Result<T, ModelError>) -> Result<T, ModelError> {
+    parsed.map_err(|e| ModelError {
+        reason: format!("{kind} {name} has bad {key}: {}", e.reason),
+    })
+}
+
+fn get_optional_float(table: &Value, key: &str) -> Option<f64> {
+    match table.get(key) {
+        Some(Value::Float(value)) => Some(*value),
+        Some(Value::Integer(value)) => Some(*value as f64),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use toml::Value;
+
+    use super::parse_config;
+
+    const NETWORK: &str = r#"
+[[networks]]
+id = 1
+chain_id = 1
+name = "ethereum"
+rpc_url = "http://localhost:8545"
+max_step = 1000
+"#;
+
+    fn parse_error(config: &str) -> String {
+        match parse_config(&config.parse::<Value>().unwrap()) {
+            Ok(_) => panic!("config should be rejected:{config}"),
+            Err(e) => e.reason,
+        }
+    }
+
+    #[test]
+    fn accepts_minimal_config() {
+        let config = format!("{NETWORK}\n[[tokens]]\nid = 1\nname = \"usdt\"\naddress = \"0xdac17f958d2ee523a2206206994597c13d831ec7\"\ntype = \"erc20\"\nnetwork = \"ethereum\"\n");
+        let config = parse_config(&config.parse::<Value>().unwrap()).unwrap();
+        assert_eq!(config.networks[0].max_step, 1000);
+        assert_eq!(config.tokens[0].token_type.to_string(), "erc20");
+    }
+
+    #[test]
+    fn rejects_unknown_values_naming_the_entry() {
+        assert_eq!(
+            parse_error(&format!("{NETWORK}head_block_tag = \"newest\"\n")),
+            "Network ethereum has bad head_block_tag: Not implemented HeadBlockTag newest"
+        );
+        assert_eq!(
+            parse_error(&format!("{NETWORK}trace_api = \"debug\"\n")),
+            "Network ethereum has bad trace_api: Not implemented TraceApi debug"
+        );
+        assert_eq!(
+            parse_error(&format!("{NETWORK}\n[[tokens]]\nid = 1\nname = \"usdt\"\naddress = \"0x0\"\ntype = \"erc777\"\nnetwork = \"ethereum\"\n")),
+            "Token usdt has bad type: Not implemented TokenType erc777"
+        );
+    }
+
+    #[test]
+    fn rejects_duplicate_ids_names_and_addresses() {
+        let token = |id: i64, name: &str, address: &str, token_type: &str| {
+            format!("\n[[tokens]]\nid = {id}\nname = \"{name}\"\naddress = \"{address}\"\ntype = \"{token_type}\"\nnetwork = \"ethereum\"\n")
+        };
+        let indexer = |id: i64, name: &str| format!("\n[[indexers]]\nid = {id}\nname = \"{name}\"\nnetwork = \"ethereum\"\n");
+        let second_network = NETWORK.replace("id = 1\nchain_id = 1\nname = \"ethereum\"", "id = 2\nchain_id = 1\nname = \"ethereum\"");
+        assert_eq!(parse_error(&format!("{NETWORK}{second_network}")), "Config has more than one network with name ethereum");
+        assert_eq!(
+            parse_error(&format!("{NETWORK}{}{}", token(1, "usdt", "0x1", "erc20"), token(1, "usdc", "0x2", "erc20"))),
+            "Config has more than one token with id 1"
+        );
+        assert_eq!(
+            parse_error(&format!("{NETWORK}{}{}", token(1, "usdt", "0x1", "erc20"), token(2, "usdt", "0x2", "erc20"))),
+            "Config has more than one token with name usdt on network ethereum"
+        );
+        assert_eq!(
+            parse_error(&format!("{NETWORK}{}{}", token(1, "usdt", "0xAB", "erc20"), token(2, "nft", "0xab", "erc721"))),
+            "Config has more than one token with address 0xab on network ethereum"
+        );
+        assert_eq!(
+            parse_error(&format!("{NETWORK}{}{}", indexer(1, "usdt"), indexer(1, "usdc"))),
+            "Config has more than one indexer with id 1"
+        );
+        assert_eq!(
+            parse_error(&format!("{NETWORK}{}{}", indexer(1, "usdt"), indexer(2, "usdt"))),
+            "Config has more than one indexer with name usdt"
+        );
+        // native token is not routed by address, so it may share one with a contract
+        let config = format!("{NETWORK}{}{}", token(1, "nft", "0x0", "erc721"), token(2, "eth", "0x0", "native"));
+        assert!(parse_config(&config.parse::<Value>().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn rejects_negative_max_step() {
+        assert!(parse_error(&NETWORK.replace("max_step = 1000", "max_step = -1")).contains("has negative max_step -1"));
+    }
+}
@@ -0,0 +1,50 @@
+use std::fmt::Display;
+
+use web3::types::BlockNumber;
+
+use super::ModelError;
+
+#[derive(Clone)]
+pub enum HeadBlockTag {
+    Latest,
+    Safe,
+    Finalized,
+}
+const HEAD_BLOCK_TAG_LATEST: &str = "latest";
+const HEAD_BLOCK_TAG_SAFE: &str = "safe";
+const HEAD_BLOCK_TAG_FINALIZED: &str = "finalized";
+
+impl Display for HeadBlockTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Latest => write!(f, "{}", HEAD_BLOCK_TAG_LATEST),
+            Self::Safe => write!(f, "{}", HEAD_BLOCK_TAG_SAFE),
+            Self::Finalized => write!(f, "{}", HEAD_BLOCK_TAG_FINALIZED),
+        }
+    }
+}
+
+impl TryFrom<&String> for HeadBlockTag {
+    type Error = ModelError;
+
+    fn try_from(value: &String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            HEAD_BLOCK_TAG_LATEST => Ok(Self::Latest),
+            HEAD_BLOCK_TAG_SAFE => Ok(Self::Safe),
+            HEAD_BLOCK_TAG_FINALIZED => Ok(Self::Finalized),
+            _ => Err(ModelError {
+                reason: format!("Not implemented HeadBlockTag {value}"),
+            }),
+        }
+    }
+}
+
+impl HeadBlockTag {
+    pub fn as_block_number(&self) -> BlockNumber {
+        match self {
+            Self::Latest => BlockNumber::Latest,
+            Self::Safe => BlockNumber::Safe,
+            Self::Finalized => BlockNumber::Finalized,
+        }
+    }
+}
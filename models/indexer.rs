@@ -1,12 +1,28 @@
-use postgres::{Client, Row};
+use tokio_postgres::{Client, Row};
 use serde_json::Value;
 
 use std::{convert::From, fmt::Display};
 
-use super::ModelError;
+use super::{
+    file_config::{file_config, IndexerDefinition},
+    indexer_status::{INACTIVE_STATUSES, SELF_REPORTED_STATUSES},
+    IndexerStatus, ModelError,
+};
 
-const TABLE_NAME: &'static str = "indexer_api_indexer";
+const TABLE_NAME: &str = "indexer_api_indexer";
+// progress of indexers defined in a config file
+const STATE_TABLE_NAME: &str = "indexer_state";
 
+/// Table holding last block and status, which is the indexer table itself unless config comes from a file.
+fn state_table_name() -> &'static str {
+    match file_config() {
+        Some(_) => STATE_TABLE_NAME,
+        None => TABLE_NAME,
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone)]
 pub struct Indexer {
     pub id: i64,
     pub name: String,
@@ -18,20 +34,30 @@ pub struct Indexer {
     pub network_id: i64,
     pub status: String,
     pub indexer_type: String,
+    pub reindex_block: Option<u64>,
+    pub start_block: Option<u64>,
+    pub end_block: Option<u64>,
+    pub enrich_transactions: bool,
+    pub index_internal_transfers: bool,
 }
 
 impl Indexer {
-    pub fn update_last_block(
+    /// Moves last block forward only, so a backfill running next to the live cycle never moves it back.
+    /// Rollbacks move it back with `add_last_block_to_db_tx`.
+    pub async fn update_last_block(
         &mut self,
         client: &mut Client,
         new_last_block: u64,
     ) -> Result<(), ModelError> {
         let new_last_block_casted = new_last_block as i64;
-        let query = format!("UPDATE {} SET last_block = $1 WHERE name = $2", TABLE_NAME);
-        let result = client.execute(query.as_str(), &[&new_last_block_casted, &self.name]);
+        let query = format!(
+            "UPDATE {} SET last_block = GREATEST(last_block, $1) WHERE name = $2 RETURNING last_block",
+            state_table_name()
+        );
+        let result = client.query_opt(query.as_str(), &[&new_last_block_casted, &self.name]).await;
         match result {
-            Ok(_) => {
-                self.last_block = new_last_block as u64;
+            Ok(row) => {
+                self.last_block = row.map_or(new_last_block, |row| row.get::<usize, i64>(0) as u64);
                 Ok(())
             },
             Err(e) => {
@@ -40,9 +66,29 @@ impl Indexer {
         }
     }
 
-    pub fn load_from_db(client: &mut Client, name: &String) -> Result<Self, ModelError> {
+    pub async fn add_last_block_to_db_tx(
+        &self,
+        db_tx: &mut tokio_postgres::Transaction<'_>,
+        new_last_block: u64,
+    ) -> Result<(), ModelError> {
+        let query = format!("UPDATE {} SET last_block = $1 WHERE name = $2", state_table_name());
+        match db_tx.execute(query.as_str(), &[&(new_last_block as i64), &self.name]).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(ModelError { reason: e.to_string() }),
+        }
+    }
+
+    pub async fn load_from_db(client: &mut Client, name: &String) -> Result<Self, ModelError> {
+        if let Some(config) = file_config() {
+            return match config.indexers.iter().find(|definition| &definition.indexer.name == name) {
+                Some(definition) => Self::load_from_state(client, definition).await,
+                None => Err(ModelError {
+                    reason: format!("Indexer {name} is not defined in config"),
+                }),
+            };
+        }
         let query = format!("SELECT * FROM {} WHERE name = $1", TABLE_NAME);
-        match client.query(query.as_str(), &[&name]) {
+        match client.query(query.as_str(), &[&name]).await {
             Ok(indexers_rows) => {
                 if indexers_rows.len() != 1 {
                     return Err(ModelError {
@@ -51,19 +97,121 @@ impl Indexer {
                         ),
                     });
                 }
-                let indexer_row = indexers_rows.get(0).unwrap();
+                let indexer_row = indexers_rows.first().unwrap();
                 Ok(Self::from_row(indexer_row))
             }
             Err(e) => {
-                return Err(ModelError {
+                Err(ModelError {
                     reason: e.to_string(),
                 })
             }
         }
     }
 
-    pub fn refresh(&mut self, client: &mut Client) -> Result<(), ModelError> {
-        match Self::load_from_db(client, &self.name) {
+    pub async fn load_active_from_db(client: &mut Client) -> Result<Vec<Self>, ModelError> {
+        if file_config().is_some() {
+            let indexers = Self::load_all_from_db(client).await?;
+            return Ok(indexers
+                .into_iter()
+                .filter(|indexer| !INACTIVE_STATUSES.contains(&indexer.status.as_str()))
+                .collect());
+        }
+        let query = format!("SELECT * FROM {} WHERE status <> ALL($1) ORDER BY id", TABLE_NAME);
+        match client.query(query.as_str(), &[&INACTIVE_STATUSES]).await {
+            Ok(rows) => Ok(rows.iter().map(Self::from_row).collect()),
+            Err(e) => Err(ModelError {
+                reason: e.to_string(),
+            }),
+        }
+    }
+
+    pub async fn load_all_from_db(client: &mut Client) -> Result<Vec<Self>, ModelError> {
+        if let Some(config) = file_config() {
+            let mut indexers = vec![];
+            for definition in config.indexers.iter() {
+                indexers.push(Self::load_from_state(client, definition).await?);
+            }
+            return Ok(indexers);
+        }
+        let query = format!("SELECT * FROM {} ORDER BY id", TABLE_NAME);
+        match client.query(query.as_str(), &[]).await {
+            Ok(rows) => Ok(rows.iter().map(Self::from_row).collect()),
+            Err(e) => Err(ModelError {
+                reason: e.to_string(),
+            }),
+        }
+    }
+
+    /// Combines indexer defined in config with its progress, starting from scratch on first run.
+    async fn load_from_state(client: &mut Client, definition: &IndexerDefinition) -> Result<Self, ModelError> {
+        let indexer = &definition.indexer;
+        let query = format!("INSERT INTO {STATE_TABLE_NAME} (name, status) VALUES ($1, $2) ON CONFLICT (name) DO NOTHING");
+        if let Err(e) = client.execute(query.as_str(), &[&indexer.name, &indexer.status]).await {
+            return Err(ModelError {
+                reason: format!("During creating state of indexer {} occurred {e}", indexer.name),
+            });
+        }
+        let query = format!("SELECT last_block, status, reindex_block FROM {STATE_TABLE_NAME} WHERE name = $1");
+        match client.query_one(query.as_str(), &[&indexer.name]).await {
+            Ok(row) => Ok(Self {
+                last_block: row.get::<&str, i64>("last_block") as u64,
+                status: row.get("status"),
+                reindex_block: row.get::<&str, Option<i64>>("reindex_block").map(|block| block as u64),
+                ..indexer.clone()
+            }),
+            Err(e) => Err(ModelError {
+                reason: format!("During loading state of indexer {} occurred {e}", indexer.name),
+            }),
+        }
+    }
+
+    /// Asks the running indexer to roll back to `reindex_block` and index forward from there.
+    pub async fn request_reindex(&mut self, client: &mut Client, reindex_block: u64) -> Result<(), ModelError> {
+        let query = format!("UPDATE {} SET status = $1, reindex_block = $2 WHERE name = $3", state_table_name());
+        let new_status = IndexerStatus::Reindex.to_string();
+        match client.execute(query.as_str(), &[&new_status, &(reindex_block as i64), &self.name]).await {
+            Ok(_) => {
+                self.status = new_status;
+                self.reindex_block = Some(reindex_block);
+                Ok(())
+            }
+            Err(e) => Err(ModelError { reason: e.to_string() }),
+        }
+    }
+
+    pub async fn update_status(
+        &mut self,
+        client: &mut Client,
+        status: IndexerStatus,
+    ) -> Result<(), ModelError> {
+        let new_status = status.to_string();
+        // command set by operator meanwhile must not be overwritten
+        let query = format!("UPDATE {} SET status = $1 WHERE name = $2 AND status = ANY($3)", state_table_name());
+        match client.execute(query.as_str(), &[&new_status, &self.name, &SELF_REPORTED_STATUSES]).await {
+            Ok(1) => {
+                self.status = new_status;
+                Ok(())
+            }
+            Ok(_) => Ok(()),
+            Err(e) => Err(ModelError { reason: e.to_string() }),
+        }
+    }
+
+    /// Marks requested reindex as done: clears requested block and resumes indexer.
+    pub async fn add_finish_reindex_to_db_tx(
+        &self,
+        db_tx: &mut tokio_postgres::Transaction<'_>,
+    ) -> Result<(), ModelError> {
+        let query = format!("UPDATE {} SET status = $1, reindex_block = NULL WHERE name = $2", state_table_name());
+        let new_status = IndexerStatus::Running.to_string();
+        match db_tx.execute(query.as_str(), &[&new_status, &self.name]).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(ModelError { reason: e.to_string() }),
+        }
+    }
+
+    pub async fn refresh(&mut self, client: &mut Client) -> Result<(), ModelError> {
+        match Self::load_from_db(client, &self.name).await {
             Ok(fresh_from_db) => {
                 *self = fresh_from_db;
                 Ok(())
@@ -72,6 +220,11 @@ impl Indexer {
         }
     }
 
+    /// Bounded indexer is done once it indexed its end block.
+    pub fn reached_end_block(&self) -> bool {
+        self.end_block.is_some_and(|end_block| self.last_block >= end_block)
+    }
+
     pub fn from_row(row: &Row) -> Self {
         Self {
             id: row.get(0),
@@ -84,6 +237,13 @@ impl Indexer {
             network_id: row.get(7),
             status: row.get(8),
             indexer_type: row.get(9),
+            // columns added by indexer migrations are fetched by name,
+            // falling back to their defaults so read-only commands work on a database not migrated yet
+            reindex_block: row.try_get::<&str, Option<i64>>("reindex_block").ok().flatten().map(|block| block as u64),
+            start_block: row.try_get::<&str, Option<i64>>("start_block").ok().flatten().map(|block| block as u64),
+            end_block: row.try_get::<&str, Option<i64>>("end_block").ok().flatten().map(|block| block as u64),
+            enrich_transactions: row.try_get("enrich_transactions").unwrap_or(false),
+            index_internal_transfers: row.try_get("index_internal_transfers").unwrap_or(false),
         }
     }
 }
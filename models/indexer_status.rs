@@ -0,0 +1,76 @@
+use std::fmt::Display;
+
+use super::ModelError;
+
+#[derive(PartialEq)]
+pub enum IndexerStatus {
+    Running,
+    Synced,
+    Error,
+    Paused,
+    Stopped,
+    Reindex,
+    Finished,
+    Interrupted,
+}
+const INDEXER_STATUS_RUNNING: &str = "running";
+const INDEXER_STATUS_SYNCED: &str = "synced";
+const INDEXER_STATUS_ERROR: &str = "error";
+const INDEXER_STATUS_PAUSED: &str = "paused";
+const INDEXER_STATUS_STOPPED: &str = "stopped";
+const INDEXER_STATUS_REINDEX: &str = "reindex";
+const INDEXER_STATUS_FINISHED: &str = "finished";
+// shut down by signal, resumes on next start
+const INDEXER_STATUS_INTERRUPTED: &str = "interrupted";
+// values used by Django admin before indexer reported its own states
+const INDEXER_STATUS_LEGACY_ON: &str = "on";
+const INDEXER_STATUS_LEGACY_OFF: &str = "off";
+
+pub const INACTIVE_STATUSES: &[&str] = &[
+    INDEXER_STATUS_STOPPED,
+    INDEXER_STATUS_LEGACY_OFF,
+    INDEXER_STATUS_FINISHED,
+];
+// statuses which indexer may overwrite; the rest are operator commands
+pub const SELF_REPORTED_STATUSES: &[&str] = &[
+    INDEXER_STATUS_RUNNING,
+    INDEXER_STATUS_SYNCED,
+    INDEXER_STATUS_ERROR,
+    INDEXER_STATUS_INTERRUPTED,
+    INDEXER_STATUS_LEGACY_ON,
+];
+
+impl Display for IndexerStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Running => write!(f, "{}", INDEXER_STATUS_RUNNING),
+            Self::Synced => write!(f, "{}", INDEXER_STATUS_SYNCED),
+            Self::Error => write!(f, "{}", INDEXER_STATUS_ERROR),
+            Self::Paused => write!(f, "{}", INDEXER_STATUS_PAUSED),
+            Self::Stopped => write!(f, "{}", INDEXER_STATUS_STOPPED),
+            Self::Reindex => write!(f, "{}", INDEXER_STATUS_REINDEX),
+            Self::Finished => write!(f, "{}", INDEXER_STATUS_FINISHED),
+            Self::Interrupted => write!(f, "{}", INDEXER_STATUS_INTERRUPTED),
+        }
+    }
+}
+
+impl TryFrom<&String> for IndexerStatus {
+    type Error = ModelError;
+
+    fn try_from(value: &String) -> Result<Self, ModelError> {
+        match value.as_str() {
+            INDEXER_STATUS_RUNNING | INDEXER_STATUS_LEGACY_ON => Ok(Self::Running),
+            INDEXER_STATUS_SYNCED => Ok(Self::Synced),
+            INDEXER_STATUS_ERROR => Ok(Self::Error),
+            INDEXER_STATUS_PAUSED => Ok(Self::Paused),
+            INDEXER_STATUS_STOPPED | INDEXER_STATUS_LEGACY_OFF => Ok(Self::Stopped),
+            INDEXER_STATUS_REINDEX => Ok(Self::Reindex),
+            INDEXER_STATUS_FINISHED => Ok(Self::Finished),
+            INDEXER_STATUS_INTERRUPTED => Ok(Self::Interrupted),
+            _ => Err(ModelError {
+                reason: format!("Not implemented IndexerStatus {value}"),
+            }),
+        }
+    }
+}
@@ -5,9 +5,9 @@ pub enum IndexerStrategy {
     Sender,
     TokenScan,
 }
-const TOKEN_STRATEGY_RECIPIENT: &'static str = "recipient";
-const TOKEN_STRATEGY_SENDER: &'static str = "sender";
-const TOKEN_STRATEGY_TOKEN_SCAN: &'static str = "token_scan";
+const TOKEN_STRATEGY_RECIPIENT: &str = "recipient";
+const TOKEN_STRATEGY_SENDER: &str = "sender";
+const TOKEN_STRATEGY_TOKEN_SCAN: &str = "token_scan";
 
 impl Display for IndexerStrategy {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -15,7 +15,6 @@ impl Display for IndexerStrategy {
             Self::Recipient => write!(f, "{}", TOKEN_STRATEGY_RECIPIENT),
             Self::Sender => write!(f, "{}", TOKEN_STRATEGY_SENDER),
             Self::TokenScan => write!(f, "{}", TOKEN_STRATEGY_TOKEN_SCAN),
-            _ => Err(std::fmt::Error),
         }
     }
 }
@@ -4,12 +4,26 @@ mod commons;
 mod token;
 mod token_type;
 mod indexer_strategy;
+mod head_block_tag;
+mod trace_api;
+mod indexer_status;
 mod token_transfer;
+mod block_hash;
+mod backfill_chunk;
+mod schema;
+mod file_config;
 pub use indexer::Indexer;
 pub use commons::ModelError;
 pub use network::Network;
 pub use token::Token;
 pub use token_type::TokenType;
 pub use indexer_strategy::IndexerStrategy;
+pub use head_block_tag::HeadBlockTag;
+pub use trace_api::TraceApi;
+pub use indexer_status::IndexerStatus;
 pub use token_transfer::TokenTransfer;
-pub use token_type::{ERC20_TRANSFER, ERC721_TRANSFER, ERC1155_TRANSFER_SINGLE, ERC1155_TRANSFER_BATCH};
+pub use block_hash::BlockHash;
+pub use backfill_chunk::BackfillChunk;
+pub use schema::migrate;
+pub use file_config::load_file_config;
+pub use token_type::{ERC1155_TRANSFER_SINGLE, ERC1155_TRANSFER_BATCH};
@@ -1,24 +1,45 @@
-use postgres::{Client, Row};
+use log::warn;
+use tokio_postgres::{Client, Row};
 
-use super::ModelError;
+use super::{file_config::file_config, HeadBlockTag, ModelError, TraceApi};
 
-const TABLE_NAME: &'static str = "indexer_api_network";
+const TABLE_NAME: &str = "indexer_api_network";
+pub const DEFAULT_RPC_TIMEOUT_SECONDS: u64 = 30;
+pub const DEFAULT_RPC_CONNECT_TIMEOUT_SECONDS: u64 = 10;
 
+#[allow(dead_code)]
+#[derive(Clone)]
 pub struct Network {
     pub id: i64,
     pub chain_id: i64,
     pub name: String,
     pub rpc_url: String,
+    pub fallback_rpc_urls: Vec<String>,
     pub max_step: u64,
     pub network_type: String,
     pub need_poa: bool,
     pub explorer_url: String,
+    pub confirmations: u64,
+    pub head_block_tag: HeadBlockTag,
+    pub rpc_requests_per_second: Option<f64>,
+    pub rpc_compute_units_per_second: Option<f64>,
+    pub trace_api: Option<TraceApi>,
+    pub rpc_timeout_seconds: u64,
+    pub rpc_connect_timeout_seconds: u64,
 }
 
 impl Network {
-    pub fn load_from_db(client: &mut Client, network_id: i64) -> Result<Self, ModelError> {
+    pub async fn load_from_db(client: &mut Client, network_id: i64) -> Result<Self, ModelError> {
+        if let Some(config) = file_config() {
+            return match config.networks.iter().find(|network| network.id == network_id) {
+                Some(network) => Ok(network.clone()),
+                None => Err(ModelError {
+                    reason: format!("Network {network_id} is not defined in config"),
+                }),
+            };
+        }
         let query = format!("SELECT * FROM {} WHERE id = $1", TABLE_NAME);
-        match client.query(query.as_str(), &[&network_id]) {
+        match client.query(query.as_str(), &[&network_id]).await {
             Ok(networks_rows) => {
                 if networks_rows.len() != 1 {
                     return Err(ModelError {
@@ -28,27 +49,126 @@ impl Network {
                         ),
                     });
                 }
-                let network_row = networks_rows.get(0).unwrap();
-                Ok(Self::from_row(network_row))
+                let network_row = networks_rows.first().unwrap();
+                Self::from_row(network_row)
             }
             Err(e) => {
-                return Err(ModelError {
+                Err(ModelError {
                     reason: e.to_string(),
                 })
             }
         }
     }
 
-    pub fn from_row(row: &Row) -> Self {
-        Self {
+    pub async fn load_all_from_db(client: &mut Client) -> Result<Vec<Self>, ModelError> {
+        if let Some(config) = file_config() {
+            return Ok(config.networks.clone());
+        }
+        let query = format!("SELECT * FROM {} ORDER BY id", TABLE_NAME);
+        match client.query(query.as_str(), &[]).await {
+            Ok(rows) => rows.iter().map(Self::from_row).collect(),
+            Err(e) => Err(ModelError {
+                reason: e.to_string(),
+            }),
+        }
+    }
+
+    pub fn from_row(row: &Row) -> Result<Self, ModelError> {
+        let name: String = row.get(2);
+        let head_block_tag = match HeadBlockTag::try_from(
+            &row.try_get::<&str, String>("head_block_tag").unwrap_or_else(|_| HeadBlockTag::Latest.to_string()),
+        ) {
+            Ok(head_block_tag) => head_block_tag,
+            Err(e) => {
+                return Err(ModelError {
+                    reason: format!("Network {name} has bad head_block_tag: {}", e.reason),
+                })
+            }
+        };
+        let trace_api = match row.try_get::<&str, Option<String>>("trace_api").ok().flatten() {
+            Some(trace_api) => match TraceApi::try_from(&trace_api) {
+                Ok(trace_api) => Some(trace_api),
+                Err(e) => {
+                    return Err(ModelError {
+                        reason: format!("Network {name} has bad trace_api: {}", e.reason),
+                    })
+                }
+            },
+            None => None,
+        };
+        Ok(Self {
             id: row.get(0),
             chain_id: row.get(1),
-            name: row.get(2),
+            name: name.clone(),
             rpc_url: row.get(3),
             max_step: row.get::<usize, i64>(4) as u64,
             network_type: row.get(5),
             need_poa: row.get(6),
             explorer_url: row.get(7),
+            // columns added by indexer migrations are fetched by name,
+            // falling back to their defaults so read-only commands work on a database not migrated yet
+            confirmations: row.try_get::<&str, i64>("confirmations").unwrap_or(0) as u64,
+            head_block_tag,
+            fallback_rpc_urls: row.try_get("fallback_rpc_urls").unwrap_or_default(),
+            rpc_requests_per_second: positive_rate_limit(
+                &name,
+                "rpc_requests_per_second",
+                row.try_get("rpc_requests_per_second").ok().flatten(),
+            ),
+            rpc_compute_units_per_second: positive_rate_limit(
+                &name,
+                "rpc_compute_units_per_second",
+                row.try_get("rpc_compute_units_per_second").ok().flatten(),
+            ),
+            trace_api,
+            rpc_timeout_seconds: positive_timeout(
+                &name,
+                "rpc_timeout_seconds",
+                row.try_get("rpc_timeout_seconds").ok().flatten(),
+                DEFAULT_RPC_TIMEOUT_SECONDS,
+            ),
+            rpc_connect_timeout_seconds: positive_timeout(
+                &name,
+                "rpc_connect_timeout_seconds",
+                row.try_get("rpc_connect_timeout_seconds").ok().flatten(),
+                DEFAULT_RPC_CONNECT_TIMEOUT_SECONDS,
+            ),
+        })
+    }
+
+    /// Primary rpc url followed by fallbacks, in order of preference.
+    pub fn get_rpc_urls(&self) -> Vec<String> {
+        let mut rpc_urls = vec![self.rpc_url.clone()];
+        for rpc_url in self.fallback_rpc_urls.iter() {
+            if !rpc_urls.contains(rpc_url) {
+                rpc_urls.push(rpc_url.clone());
+            }
+        }
+        rpc_urls
+    }
+}
+
+/// Zero limit would stall every call of the network and a negative one would silently disable it,
+/// so both are ignored.
+pub fn positive_rate_limit(network_name: &str, key: &str, limit: Option<f64>) -> Option<f64> {
+    match limit {
+        Some(limit) if limit > 0.0 && limit.is_finite() => Some(limit),
+        Some(limit) => {
+            warn!("Network {network_name} has {key} {limit} which is not positive, it is not limited by it");
+            None
+        }
+        None => None,
+    }
+}
+
+/// Timeout in seconds, the default one if it is not set or not positive.
+pub fn positive_timeout(network_name: &str, key: &str, seconds: Option<i64>, default: u64) -> u64 {
+    match seconds {
+        Some(seconds) if seconds > 0 => seconds as u64,
+        Some(seconds) => {
+            warn!("Network {network_name} has {key} {seconds} which is not positive, {default}s is used instead");
+            default
         }
+        None => default,
     }
 }
@@ -0,0 +1,90 @@
+use tokio_postgres::Client;
+
+use super::{file_config::file_config, ModelError};
+
+// Django-owned tables which do not exist when configuration comes from a file
+const CONFIG_TABLES: &[&str] = &["indexer_api_indexer", "indexer_api_network"];
+
+// tables below are owned by the indexer itself, unlike Django-owned indexer_api_* tables
+const MIGRATIONS: &[&str] = &[
+    // created by Django when it is deployed, by the indexer itself otherwise
+    "CREATE TABLE IF NOT EXISTS indexer_api_tokentransfer (
+        id BIGSERIAL PRIMARY KEY,
+        operator VARCHAR(42) NOT NULL,
+        sender VARCHAR(42) NOT NULL,
+        recipient VARCHAR(42) NOT NULL,
+        tx_hash VARCHAR(66) NOT NULL,
+        token_id NUMERIC(78, 0) NOT NULL,
+        amount NUMERIC(78, 0) NOT NULL,
+        token_instance_id BIGINT NOT NULL,
+        fetched_by_id BIGINT NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS indexer_block_hash (
+        indexer_id BIGINT NOT NULL,
+        block_number BIGINT NOT NULL,
+        block_hash VARCHAR(66) NOT NULL,
+        PRIMARY KEY (indexer_id, block_number)
+    )",
+    "ALTER TABLE indexer_api_tokentransfer ADD COLUMN IF NOT EXISTS block_number BIGINT",
+    "ALTER TABLE indexer_api_network ADD COLUMN IF NOT EXISTS confirmations BIGINT NOT NULL DEFAULT 0",
+    "ALTER TABLE indexer_api_network ADD COLUMN IF NOT EXISTS head_block_tag VARCHAR(16) NOT NULL DEFAULT 'latest'",
+    "ALTER TABLE indexer_api_indexer ADD COLUMN IF NOT EXISTS reindex_block BIGINT",
+    "ALTER TABLE indexer_api_network ADD COLUMN IF NOT EXISTS fallback_rpc_urls TEXT[] NOT NULL DEFAULT '{}'",
+    "ALTER TABLE indexer_api_network ADD COLUMN IF NOT EXISTS rpc_requests_per_second DOUBLE PRECISION",
+    "ALTER TABLE indexer_api_network ADD COLUMN IF NOT EXISTS rpc_compute_units_per_second DOUBLE PRECISION",
+    "CREATE TABLE IF NOT EXISTS indexer_backfill_chunk (
+        indexer_id BIGINT NOT NULL,
+        from_block BIGINT NOT NULL,
+        to_block BIGINT NOT NULL,
+        PRIMARY KEY (indexer_id, from_block)
+    )",
+    "ALTER TABLE indexer_api_indexer ADD COLUMN IF NOT EXISTS start_block BIGINT",
+    "ALTER TABLE indexer_api_indexer ADD COLUMN IF NOT EXISTS end_block BIGINT",
+    "ALTER TABLE indexer_api_tokentransfer ADD COLUMN IF NOT EXISTS block_hash VARCHAR(66)",
+    "ALTER TABLE indexer_api_tokentransfer ADD COLUMN IF NOT EXISTS log_index BIGINT",
+    "ALTER TABLE indexer_api_tokentransfer ADD COLUMN IF NOT EXISTS transaction_index BIGINT",
+    // rows saved before log index was recorded have NULL in it and never conflict.
+    // a native transfer and a token event of one transaction may share position, token tells them apart
+    "CREATE UNIQUE INDEX IF NOT EXISTS indexer_tokentransfer_event_token_key
+        ON indexer_api_tokentransfer (fetched_by_id, block_number, log_index, tx_hash, token_id, token_instance_id)",
+    // narrower key of earlier versions, replaced by the one above
+    "DROP INDEX IF EXISTS indexer_tokentransfer_event_key",
+    "ALTER TABLE indexer_api_tokentransfer ADD COLUMN IF NOT EXISTS block_timestamp TIMESTAMPTZ",
+    "ALTER TABLE indexer_api_indexer ADD COLUMN IF NOT EXISTS enrich_transactions BOOLEAN NOT NULL DEFAULT FALSE",
+    "ALTER TABLE indexer_api_tokentransfer ADD COLUMN IF NOT EXISTS tx_origin VARCHAR(42)",
+    "ALTER TABLE indexer_api_tokentransfer ADD COLUMN IF NOT EXISTS gas_used NUMERIC(78, 0)",
+    "ALTER TABLE indexer_api_tokentransfer ADD COLUMN IF NOT EXISTS effective_gas_price NUMERIC(78, 0)",
+    "ALTER TABLE indexer_api_tokentransfer ADD COLUMN IF NOT EXISTS tx_status SMALLINT",
+    // NULL for networks whose nodes do not serve traces
+    "ALTER TABLE indexer_api_network ADD COLUMN IF NOT EXISTS trace_api VARCHAR(16)",
+    "ALTER TABLE indexer_api_indexer ADD COLUMN IF NOT EXISTS index_internal_transfers BOOLEAN NOT NULL DEFAULT FALSE",
+    // NULL keeps default timeouts of HTTP calls
+    "ALTER TABLE indexer_api_network ADD COLUMN IF NOT EXISTS rpc_timeout_seconds BIGINT",
+    "ALTER TABLE indexer_api_network ADD COLUMN IF NOT EXISTS rpc_connect_timeout_seconds BIGINT",
+    "CREATE TABLE IF NOT EXISTS indexer_state (
+        name VARCHAR(255) PRIMARY KEY,
+        last_block BIGINT NOT NULL DEFAULT 0,
+        status VARCHAR(32) NOT NULL,
+        reindex_block BIGINT
+    )",
+];
+
+pub async fn migrate(client: &mut Client) -> Result<(), ModelError> {
+    for migration in MIGRATIONS.iter() {
+        if file_config().is_some() && alters_config_table(migration) {
+            continue;
+        }
+        if let Err(e) = client.batch_execute(migration).await {
+            return Err(ModelError {
+                reason: format!("During applying migration {migration} occurred {e}"),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn alters_config_table(migration: &str) -> bool {
+    CONFIG_TABLES
+        .iter()
+        .any(|table| migration.starts_with(&format!("ALTER TABLE {table} ")))
+}
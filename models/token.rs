@@ -1,12 +1,14 @@
-use postgres::{Client, Row};
+use tokio_postgres::{Client, Row};
 
-use super::{ModelError, Indexer, TokenType};
+use super::{file_config::file_config, ModelError, Indexer, TokenType};
 
-const TABLE_NAME: &'static str = "indexer_api_token";
-const INDEXER_TOKEN_TABLE_NAME: &'static str = "indexer_api_indexer_watched_tokens";
+const TABLE_NAME: &str = "indexer_api_token";
+const INDEXER_TOKEN_TABLE_NAME: &str = "indexer_api_indexer_watched_tokens";
 
 use rust_decimal::Decimal;
 
+#[allow(dead_code)]
+#[derive(Clone)]
 pub struct Token {
     pub id: i64,
     pub address: String,
@@ -19,7 +21,8 @@ pub struct Token {
 }
 
 impl Token {
-    pub fn load_from_db(
+    #[allow(dead_code)]
+    pub async fn load_from_db(
         client: &mut Client,
         network_id: i64,
         address: String,
@@ -28,7 +31,7 @@ impl Token {
             "SELECT * FROM {} WHERE network_id = $1 AND address = $2",
             TABLE_NAME
         );
-        match client.query(query.as_str(), &[&network_id, &address]) {
+        match client.query(query.as_str(), &[&network_id, &address]).await {
             Ok(tokens_rows) => {
                 if tokens_rows.len() != 1 {
                     Err(ModelError {
@@ -38,7 +41,7 @@ impl Token {
                         ),
                     })
                 } else {
-                    Ok(Self::from_row(tokens_rows.get(0).unwrap()))
+                    Self::from_row(tokens_rows.first().unwrap())
                 }
             }
             Err(e) => Err(ModelError {
@@ -47,12 +50,18 @@ impl Token {
         }
     }
 
-    pub fn load_tokens_from_db_by_indexer(client: &mut Client, indexer: &Indexer) -> Result<Vec<Self>, ModelError> {
+    pub async fn load_tokens_from_db_by_indexer(client: &mut Client, indexer: &Indexer) -> Result<Vec<Self>, ModelError> {
+        if let Some(config) = file_config() {
+            let token_ids = match config.indexers.iter().find(|definition| definition.indexer.id == indexer.id) {
+                Some(definition) => &definition.token_ids,
+                None => return Ok(vec![]),
+            };
+            return Ok(config.tokens.iter().filter(|token| token_ids.contains(&token.id)).cloned().collect());
+        }
         let query = format!("SELECT * FROM {TABLE_NAME} INNER JOIN {INDEXER_TOKEN_TABLE_NAME} ON {TABLE_NAME}.id = {INDEXER_TOKEN_TABLE_NAME}.token_id WHERE {INDEXER_TOKEN_TABLE_NAME}.indexer_id = $1");
-        match client.query(query.as_str(), &[&indexer.id]) {
+        match client.query(query.as_str(), &[&indexer.id]).await {
             Ok(rows) => {
-                let result = rows.iter().map(Self::from_row).collect();
-                Ok(result)
+                rows.iter().map(Self::from_row).collect()
             },
             Err(e) => {
                 Err(ModelError { reason: e.to_string() })
@@ -61,16 +70,36 @@ impl Token {
 
     }
 
-    pub fn from_row(row: &Row) -> Self {
-        Self {
+    pub async fn load_all_from_db(client: &mut Client) -> Result<Vec<Self>, ModelError> {
+        if let Some(config) = file_config() {
+            return Ok(config.tokens.clone());
+        }
+        let query = format!("SELECT * FROM {TABLE_NAME} ORDER BY network_id, id");
+        match client.query(query.as_str(), &[]).await {
+            Ok(rows) => rows.iter().map(Self::from_row).collect(),
+            Err(e) => Err(ModelError { reason: e.to_string() }),
+        }
+    }
+
+    pub fn from_row(row: &Row) -> Result<Self, ModelError> {
+        let name: String = row.get(2);
+        let token_type = match TokenType::try_from(&row.get::<usize, String>(4)) {
+            Ok(token_type) => token_type,
+            Err(e) => {
+                return Err(ModelError {
+                    reason: format!("Token {name} has bad type: {}", e.reason),
+                })
+            }
+        };
+        Ok(Self {
             id: row.get(0),
             address: row.get(1),
-            name: row.get(2),
+            name,
             strategy: row.get(3),
-            token_type: TokenType::from(&row.get::<usize, String>(4)),
+            token_type,
             total_supply: row.get(5),
             volume: row.get(6),
             network_id: row.get(7),
-        }
+        })
     }
 }
@@ -1,20 +1,16 @@
 use std::vec;
 
-use postgres::Client;
-use rust_decimal::Decimal;
 use web3::types::U256;
 
 use crate::indexer::transactions::Transaction;
 
-const TABLE_NAME: &'static str = "indexer_api_tokentransfer";
-const INSERT_QUERY: &'static str = "INSERT INTO indexer_api_tokentransfer (operator, sender, recipient, tx_hash, token_id, amount, token_instance_id, fetched_by_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)";
+const TABLE_NAME: &str = "indexer_api_tokentransfer";
 
 use crate::indexer::transactions::TransferredToken::{Fungible, ERC1155, NFT};
 
 use super::{Indexer, ModelError, Token};
 
 pub struct TokenTransfer {
-    id: i64,
     operator: String,
     sender: String,
     recipient: String,
@@ -23,49 +19,76 @@ pub struct TokenTransfer {
     amount: String,
     token_instance_id: i64,
     fetched_by_id: i64,
+    block_number: i64,
+    block_hash: String,
+    log_index: i64,
+    transaction_index: i64,
+    block_timestamp: Option<i64>,
+    tx_origin: Option<String>,
+    gas_used: Option<String>,
+    effective_gas_price: Option<String>,
+    tx_status: Option<u64>,
+}
+
+fn quoted_or_null(value: &Option<String>) -> String {
+    match value {
+        Some(value) => format!("'{value}'"),
+        None => String::from("NULL"),
+    }
 }
 
 impl TokenTransfer {
-    pub fn save_many(
-        client: &mut Client,
-        transactions: Vec<Transaction>,
-        token: &Token,
-        indexer: &Indexer,
-    ) -> Result<(), ModelError> {
-        let mut db_tx = Self::start_db_tx(client)?;
-        for transaction in transactions.iter() {
-            for token_transfer in Self::build_from_transaction(transaction, token, indexer).iter() {
-                token_transfer.add_to_db_tx(&mut db_tx)?;
-            }
-        }
-        match db_tx.commit() {
+    pub async fn add_to_db_tx(&self, db_tx: &mut tokio_postgres::Transaction<'_>) -> Result<(), ModelError> {
+        let block_timestamp = match self.block_timestamp {
+            Some(timestamp) => format!("to_timestamp({timestamp})"),
+            None => String::from("NULL"),
+        };
+        let tx_status = match self.tx_status {
+            Some(status) => status.to_string(),
+            None => String::from("NULL"),
+        };
+        match db_tx.execute(
+            // re-processed range updates rows of the same event instead of duplicating them
+            format!("INSERT INTO {TABLE_NAME} (operator, sender, recipient, tx_hash, token_id, amount, token_instance_id, fetched_by_id, block_number, block_hash, log_index, transaction_index, block_timestamp, tx_origin, gas_used, effective_gas_price, tx_status) VALUES ('{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}', {}, '{}', {}, {}, {}, {}, {}, {}, {}) ON CONFLICT (fetched_by_id, block_number, log_index, tx_hash, token_id, token_instance_id) DO UPDATE SET operator = EXCLUDED.operator, sender = EXCLUDED.sender, recipient = EXCLUDED.recipient, amount = EXCLUDED.amount, token_instance_id = EXCLUDED.token_instance_id, block_hash = EXCLUDED.block_hash, transaction_index = EXCLUDED.transaction_index, block_timestamp = EXCLUDED.block_timestamp, tx_origin = EXCLUDED.tx_origin, gas_used = EXCLUDED.gas_used, effective_gas_price = EXCLUDED.effective_gas_price, tx_status = EXCLUDED.tx_status", &self.operator, &self.sender, &self.recipient, &self.tx_hash, &self.token_id, &self.amount, &self.token_instance_id, &self.fetched_by_id, &self.block_number, &self.block_hash, &self.log_index, &self.transaction_index, block_timestamp, quoted_or_null(&self.tx_origin), quoted_or_null(&self.gas_used), quoted_or_null(&self.effective_gas_price), tx_status).as_str(),
+            &[],
+        ).await {
             Ok(_) => Ok(()),
             Err(e) => Err(ModelError {
-                reason: format!("During commit transaction in database occurred {e}"),
+                reason: format!(
+                    "During preparing Transaction {} to be saved {e} occurred",
+                    &self.tx_hash
+                ),
             }),
         }
     }
 
-    fn start_db_tx(client: &mut Client) -> Result<postgres::Transaction, ModelError> {
-        match client.transaction() {
-            Ok(db_tx) => Ok(db_tx),
+    /// Rows saved before block numbers were recorded have NULL in it and are never deleted,
+    /// so ranges indexed by such versions are duplicated on reindex unless these rows are deleted by hand.
+    pub async fn delete_after_block(
+        db_tx: &mut tokio_postgres::Transaction<'_>,
+        indexer: &Indexer,
+        block_number: u64,
+    ) -> Result<u64, ModelError> {
+        let query = format!("DELETE FROM {TABLE_NAME} WHERE fetched_by_id = $1 AND block_number > $2");
+        match db_tx.execute(query.as_str(), &[&indexer.id, &(block_number as i64)]).await {
+            Ok(deleted) => Ok(deleted),
             Err(e) => Err(ModelError {
-                reason: format!("During preparing database transaction occurred {e}"),
+                reason: format!("During deleting token transfers after block {block_number} occurred {e}"),
             }),
         }
     }
 
-    pub fn add_to_db_tx(&self, db_tx: &mut postgres::Transaction) -> Result<(), ModelError> {
-        match db_tx.execute(
-            format!("INSERT INTO {TABLE_NAME} (operator, sender, recipient, tx_hash, token_id, amount, token_instance_id, fetched_by_id) VALUES ('{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}')", &self.sender, &self.sender, &self.recipient, &self.tx_hash, &self.token_id, &self.amount, &self.token_instance_id, &self.fetched_by_id).as_str(),
-            &[],
-        ) {
-            Ok(_) => Ok(()),
+    pub async fn delete_in_range(
+        db_tx: &mut tokio_postgres::Transaction<'_>,
+        indexer: &Indexer,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<u64, ModelError> {
+        let query = format!("DELETE FROM {TABLE_NAME} WHERE fetched_by_id = $1 AND block_number >= $2 AND block_number <= $3");
+        match db_tx.execute(query.as_str(), &[&indexer.id, &(from_block as i64), &(to_block as i64)]).await {
+            Ok(deleted) => Ok(deleted),
             Err(e) => Err(ModelError {
-                reason: format!(
-                    "During preparing Transaction {} to be saved {e} occurred",
-                    &self.tx_hash
-                ),
+                reason: format!("During deleting token transfers from block {from_block} to {to_block} occurred {e}"),
             }),
         }
     }
@@ -75,55 +98,54 @@ impl TokenTransfer {
         token: &Token,
         indexer: &Indexer,
     ) -> Vec<Self> {
+        let receipt = transaction.receipt.as_ref();
+        // operator named by the event, otherwise whoever sent the transaction if it is known
+        let operator = match (&transaction.operator, receipt) {
+            (Some(operator), _) => operator.clone(),
+            (None, Some(receipt)) => receipt.origin.clone(),
+            (None, None) => transaction.sender.clone(),
+        };
+        let build = |token_id: String, amount: String| Self {
+            operator: operator.clone(),
+            sender: transaction.sender.clone(),
+            recipient: transaction.recipient.clone(),
+            tx_hash: transaction.tx_hash.clone(),
+            token_id,
+            amount,
+            token_instance_id: token.id,
+            fetched_by_id: indexer.id,
+            block_number: transaction.block_number as i64,
+            block_hash: transaction.block_hash.clone(),
+            log_index: transaction.log_index as i64,
+            transaction_index: transaction.transaction_index as i64,
+            block_timestamp: transaction.block_timestamp.map(|timestamp| timestamp as i64),
+            tx_origin: receipt.map(|receipt| receipt.origin.clone()),
+            gas_used: receipt.and_then(|receipt| receipt.gas_used).map(|gas_used| gas_used.to_string()),
+            effective_gas_price: receipt
+                .and_then(|receipt| receipt.effective_gas_price)
+                .map(|gas_price| gas_price.to_string()),
+            tx_status: receipt.and_then(|receipt| receipt.status),
+        };
         match &transaction.transferred_token {
-            Fungible { address, amount } => {
-                vec![Self {
-                    id: 0,
-                    operator: transaction.sender.clone(),
-                    sender: transaction.sender.clone(),
-                    recipient: transaction.recipient.clone(),
-                    tx_hash: transaction.tx_hash.clone(),
-                    token_id: String::from("0"),
-                    amount: amount.to_string(),
-                    token_instance_id: token.id,
-                    fetched_by_id: indexer.id,
-                }]
-            }
-            NFT { address, token_id } => {
-                vec![Self {
-                    id: 0,
-                    operator: transaction.sender.clone(),
-                    sender: transaction.sender.clone(),
-                    recipient: transaction.recipient.clone(),
-                    tx_hash: transaction.tx_hash.clone(),
-                    token_id: token_id.to_string(),
-                    amount: String::from("0"),
-                    token_instance_id: token.id,
-                    fetched_by_id: indexer.id,
-                }]
-            }
+            Fungible { amount, .. } => vec![build(String::from("0"), amount.to_string())],
+            NFT { token_id, .. } => vec![build(token_id.to_string(), String::from("0"))],
             ERC1155 {
-                address,
                 token_ids,
                 amounts,
+                ..
             } => {
-                assert_eq!(token_ids.len(), amounts.len());
-                let mut result = vec![];
-                for (i, token_id) in token_ids.iter().enumerate() {
-                    let amount = amounts.get(i).unwrap();
-                    result.push(Self {
-                        id: 0,
-                        operator: transaction.sender.clone(),
-                        sender: transaction.sender.clone(),
-                        recipient: transaction.recipient.clone(),
-                        tx_hash: transaction.tx_hash.clone(),
-                        token_id: token_id.to_string(),
-                        amount: amount.to_string(),
-                        token_instance_id: token.id,
-                        fetched_by_id: indexer.id,
-                    })
+                // a batch may repeat an id, its amounts are summed as rows are unique per event and id
+                let mut merged: Vec<(U256, U256)> = vec![];
+                for (token_id, amount) in token_ids.iter().zip(amounts.iter()) {
+                    match merged.iter_mut().find(|(merged_id, _)| merged_id == token_id) {
+                        Some((_, merged_amount)) => *merged_amount = merged_amount.saturating_add(*amount),
+                        None => merged.push((*token_id, *amount)),
+                    }
                 }
-                result
+                merged
+                    .into_iter()
+                    .map(|(token_id, amount)| build(token_id.to_string(), amount.to_string()))
+                    .collect()
             }
         }
     }
@@ -2,14 +2,19 @@ use std::fmt::Display;
 
 use web3::types::H256;
 
+use super::ModelError;
+
+#[derive(Clone)]
 pub enum TokenType {
     ERC20,
     ERC721,
     ERC1155,
+    Native,
 }
-const TOKEN_TYPE_ERC20: &'static str = "erc20";
-const TOKEN_TYPE_ERC721: &'static str = "erc721";
-const TOKEN_TYPE_ERC1155: &'static str = "erc1155";
+const TOKEN_TYPE_ERC20: &str = "erc20";
+const TOKEN_TYPE_ERC721: &str = "erc721";
+const TOKEN_TYPE_ERC1155: &str = "erc1155";
+const TOKEN_TYPE_NATIVE: &str = "native";
 
 impl Display for TokenType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -17,26 +22,36 @@ impl Display for TokenType {
             Self::ERC20 => write!(f, "{}", TOKEN_TYPE_ERC20),
             Self::ERC721 => write!(f, "{}", TOKEN_TYPE_ERC721),
             Self::ERC1155 => write!(f, "{}", TOKEN_TYPE_ERC1155),
+            Self::Native => write!(f, "{}", TOKEN_TYPE_NATIVE),
         }
     }
 }
 
-impl From<&String> for TokenType {
-    fn from(value: &String) -> Self {
+impl TryFrom<&String> for TokenType {
+    type Error = ModelError;
+
+    fn try_from(value: &String) -> Result<Self, ModelError> {
         match value.as_str() {
-            TOKEN_TYPE_ERC20 => Self::ERC20,
-            TOKEN_TYPE_ERC721 => Self::ERC721,
-            TOKEN_TYPE_ERC1155 => Self::ERC1155,
-            _ => panic!("Not implemented TokenType {}", value.as_str()),
+            TOKEN_TYPE_ERC20 => Ok(Self::ERC20),
+            TOKEN_TYPE_ERC721 => Ok(Self::ERC721),
+            TOKEN_TYPE_ERC1155 => Ok(Self::ERC1155),
+            TOKEN_TYPE_NATIVE => Ok(Self::Native),
+            _ => Err(ModelError {
+                reason: format!("Not implemented TokenType {value}"),
+            }),
         }
     }
 }
 
 pub const ERC20_TRANSFER: &[u8] = b"Transfer(address,address,uint256)";
 pub const ERC721_TRANSFER: &[u8] = b"Transfer(address,address,uint256)";
-pub const ERC1155_TRANSFER_SINGLE: &[u8] = b"TransferSingle(address,address,uint256,uint256)";
-pub const ERC1155_TRANSFER_BATCH: &[u8] = b"TransferBatch(address,address,uint256[],uint256[])";
+pub const ERC1155_TRANSFER_SINGLE: &[u8] = b"TransferSingle(address,address,address,uint256,uint256)";
+pub const ERC1155_TRANSFER_BATCH: &[u8] = b"TransferBatch(address,address,address,uint256[],uint256[])";
 impl TokenType {
+    pub fn is_native(&self) -> bool {
+        matches!(self, Self::Native)
+    }
+
     pub fn get_events_hashes(&self) -> Vec<H256> {
         match self {
             Self::ERC20 => vec![H256::from_slice(&web3::signing::keccak256(ERC20_TRANSFER))],
@@ -45,6 +60,8 @@ impl TokenType {
                 H256::from_slice(&web3::signing::keccak256(ERC1155_TRANSFER_SINGLE)),
                 H256::from_slice(&web3::signing::keccak256(ERC1155_TRANSFER_BATCH)),
             ],
+            // coin of the network itself moves with transactions, not events
+            Self::Native => vec![],
         }
     }
 }
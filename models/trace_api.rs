@@ -0,0 +1,37 @@
+use std::fmt::Display;
+
+use super::ModelError;
+
+/// Tracing interface a trace-capable network node exposes.
+#[derive(Clone, Copy)]
+pub enum TraceApi {
+    Parity,
+    Geth,
+}
+// trace_block of OpenEthereum, Erigon, Nethermind and Reth
+const TRACE_API_PARITY: &str = "parity";
+// debug_traceBlockByNumber with callTracer
+const TRACE_API_GETH: &str = "geth";
+
+impl Display for TraceApi {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Parity => write!(f, "{}", TRACE_API_PARITY),
+            Self::Geth => write!(f, "{}", TRACE_API_GETH),
+        }
+    }
+}
+
+impl TryFrom<&String> for TraceApi {
+    type Error = ModelError;
+
+    fn try_from(value: &String) -> Result<Self, ModelError> {
+        match value.as_str() {
+            TRACE_API_PARITY => Ok(Self::Parity),
+            TRACE_API_GETH => Ok(Self::Geth),
+            _ => Err(ModelError {
+                reason: format!("Not implemented TraceApi {value}"),
+            }),
+        }
+    }
+}